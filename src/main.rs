@@ -1,16 +1,50 @@
-use std::process::Command;
-use std::path::PathBuf;
+use std::process::{self, Child, Command, ExitStatus, Stdio};
+use std::path::{Path, PathBuf};
+use std::ffi::OsStr;
+use std::fmt;
 use std::fs;
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use anyhow::{Result, anyhow};
-use clap::{Parser, Subcommand};
-use env_logger::Env;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use env_logger::{Env, WriteStyle};
 use log::{info, warn, error};
 use serde::{Deserialize, Serialize};
 use names::Generator;
+use regex::Regex;
+
+const AFTER_HELP: &str = "\
+Configuration precedence (highest first):
+  1. command-line flags (e.g. --remote)
+  2. environment variables: GIT_AUTOMATE_REMOTE, GIT_AUTOMATE_AUTO_PULL, GIT_AUTOMATE_COMMIT_TEMPLATE,
+     GIT_AUTOMATE_GIT_PATH
+  3. git-automate.toml
+  4. ~/.config/git-automate/config.toml ($XDG_CONFIG_HOME is honored; skipped with --no-global)
+  5. built-in defaults
+
+Exit codes:
+  0  success
+  1  unclassified failure
+  2  not inside a git repository
+  3  nothing to commit (with --require-changes or empty_commit_behavior = \"error\")
+  4  merge or rebase conflict
+  5  push rejected by the remote
+  6  git is not installed (or git_path does not point at it)
+
+Shell completion:
+  bash:        git-automate completions bash > ~/.local/share/bash-completion/completions/git-automate
+  zsh:         git-automate completions zsh > \"${fpath[1]}/_git-automate\"
+  fish:        git-automate completions fish > ~/.config/fish/completions/git-automate.fish
+  powershell:  git-automate completions powershell >> $PROFILE";
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, after_help = AFTER_HELP)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
@@ -19,332 +53,6985 @@ struct Cli {
     #[arg(short, long)]
     verbose: bool,
 
+    /// Only print errors and the output the command was asked for
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
     /// Dry run mode
     #[arg(long)]
     dry_run: bool,
+
+    /// Path to the config file (default: git-automate.toml, searched up to the repository root)
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Remote to use instead of the configured default_remote
+    #[arg(long)]
+    remote: Option<String>,
+
+    /// Assume yes for confirmation prompts
+    #[arg(short, long)]
+    yes: bool,
+
+    /// Disable colored output (also honors NO_COLOR)
+    #[arg(long)]
+    no_color: bool,
+
+    /// Stream git's progress output for pull, push and fetch instead of capturing it
+    #[arg(long)]
+    stream: bool,
+
+    /// Upgrade an older git-automate.toml to the current config version in place
+    #[arg(long)]
+    migrate: bool,
+
+    /// Ignore the user-wide config (~/.config/git-automate/config.toml)
+    #[arg(long)]
+    no_global: bool,
+
+    /// Kill git commands that run longer than this many seconds
+    #[arg(long, value_name = "SECS")]
+    timeout: Option<u64>,
+}
+
+/// Failures callers may need to tell apart. `GitOps` methods return them inside
+/// `anyhow::Error` (often with extra context), so match on them with
+/// `downcast_ref` over the error chain or through `exit_code`.
+#[derive(Debug)]
+enum GitError {
+    NotARepo,
+    NothingToCommit,
+    MergeConflict { action: String, files: Vec<String>, hint: String },
+    PushRejected(String),
+    CommandFailed { action: String, stderr: String },
+    Timeout { secs: u64 },
+    /// `None` means the default `git` lookup on PATH failed.
+    GitNotFound { path: Option<String> },
+}
+
+impl GitError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            GitError::NotARepo => 2,
+            GitError::NothingToCommit => 3,
+            GitError::MergeConflict { .. } => 4,
+            GitError::PushRejected(_) => 5,
+            GitError::GitNotFound { .. } => 6,
+            GitError::CommandFailed { .. } | GitError::Timeout { .. } => 1,
+        }
+    }
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GitError::NotARepo => write!(f, "Not in a git repository"),
+            GitError::NothingToCommit => write!(f, "No changes to commit"),
+            GitError::MergeConflict { action, files, hint } => {
+                write!(f, "{} hit conflicts in:\n  {}\n{}", action, files.join("\n  "), hint)
+            }
+            GitError::PushRejected(message) => write!(f, "{}", message),
+            GitError::CommandFailed { action, stderr } => write!(f, "{} failed: {}", action, stderr),
+            GitError::Timeout { secs } => write!(f, "git command timed out after {}s", secs),
+            GitError::GitNotFound { path: None } => {
+                write!(f, "git is not installed or not on PATH; install git or set git_path in git-automate.toml")
+            }
+            GitError::GitNotFound { path: Some(path) } => write!(
+                f,
+                "git executable not found at {} (from git_path or GIT_AUTOMATE_GIT_PATH); fix the path or unset it",
+                path
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GitError {}
+
+fn exit_code(error: &anyhow::Error) -> i32 {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<GitError>())
+        .map(GitError::exit_code)
+        .unwrap_or(1)
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Commit and push changes
-    Commit {
-        /// Custom commit message
+    Commit(Box<CommitArgs>),
+    /// Branch operations
+    Branch {
+        #[command(subcommand)]
+        cmd: BranchCommands,
+    },
+    /// Remote operations
+    Remote {
+        #[command(subcommand)]
+        cmd: RemoteCommands,
+    },
+    /// Stash operations
+    Stash {
+        #[command(subcommand)]
+        cmd: StashCommands,
+    },
+    /// Tag operations
+    Tag {
+        #[command(subcommand)]
+        cmd: TagCommands,
+    },
+    /// Work on several branches at once in separate worktrees
+    Worktree {
+        #[command(subcommand)]
+        cmd: WorktreeCommands,
+    },
+    /// Find the commit that introduced a regression with git bisect
+    Bisect {
+        #[command(subcommand)]
+        cmd: BisectCommands,
+    },
+    /// Read or write git config keys (e.g. user.name)
+    GitConfig {
+        #[command(subcommand)]
+        cmd: GitConfigCommands,
+    },
+    /// Fetch remote-tracking refs without touching the working tree
+    Fetch {
+        /// Fetch all remotes
+        #[arg(long)]
+        all: bool,
+
+        /// Remove remote-tracking refs that no longer exist on the remote
+        #[arg(long)]
+        prune: bool,
+    },
+    /// Pull upstream changes then push local commits
+    Sync {
+        /// Automatically abort the merge or rebase if pulling hits conflicts
+        #[arg(long)]
+        abort_on_conflict: bool,
+    },
+    /// Push the current branch and open a GitHub pull request with the gh CLI
+    Pr {
+        /// Pull request title (default: the last commit subject)
+        #[arg(long)]
+        title: Option<String>,
+
+        /// Branch to merge into (default: the repository's default branch)
+        #[arg(long)]
+        base: Option<String>,
+
+        /// Open the pull request as a draft
+        #[arg(long)]
+        draft: bool,
+    },
+    /// Delete local branches that are already merged
+    Prune {
+        /// Only list the merged branches without deleting them
+        #[arg(long = "list")]
+        dry_run_list: bool,
+
+        /// Branch the others must be merged into (default: the current branch)
+        #[arg(long, value_name = "BRANCH")]
+        merged_into: Option<String>,
+
+        /// Also drop remote-tracking refs whose remote branch is gone (git remote prune)
+        #[arg(long)]
+        remote_prune: bool,
+    },
+    /// Discard working-tree changes to files, or unstage them with --staged
+    Restore {
+        #[arg(required = true)]
+        files: Vec<String>,
+
+        /// Unstage the files instead, keeping their working-tree changes
+        #[arg(long)]
+        staged: bool,
+    },
+    /// Remove untracked files (previews only, unless --force is given)
+    Clean {
+        /// Remove untracked directories as well
+        #[arg(short, long)]
+        directories: bool,
+
+        /// Actually delete the files instead of listing them
+        #[arg(short, long)]
+        force: bool,
+
+        /// Also remove files ignored by .gitignore
+        #[arg(long)]
+        ignored: bool,
+    },
+    /// Print a Markdown changelog built from conventional commit subjects
+    Changelog {
+        /// Tag or ref to start from (default: the most recent tag)
+        since: Option<String>,
+
+        /// Write the changelog to a file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Tag a release with a generated changelog and push it
+    Release {
+        /// Version to release; the tag is named v<VERSION>
+        version: String,
+
+        /// Create the tag locally without pushing the tag or the branch
+        #[arg(long)]
+        no_push: bool,
+    },
+    /// Squash the current branch's commits since it diverged from another branch into one
+    Squash {
+        /// Branch the current branch was started from
+        onto: String,
+
+        /// Message for the squashed commit (default: generated from commit_template)
+        #[arg(short, long)]
+        message: Option<String>,
+    },
+    /// Poll for changes and commit and push them once they settle (Ctrl-C to stop)
+    Watch {
+        /// Seconds between checks for changes
+        #[arg(long, default_value_t = 2)]
+        interval: u64,
+
+        /// Seconds the working tree must stay unchanged before committing
+        #[arg(long, default_value_t = 5)]
+        debounce: u64,
+    },
+    /// Commit everything as a work-in-progress checkpoint (no pull, no hooks)
+    Wip {
+        /// Push the checkpoint after committing
+        #[arg(long)]
+        push: bool,
+    },
+    /// Rebase the current branch onto another branch
+    Rebase {
+        /// Branch or commit to rebase onto
+        onto: String,
+
+        /// Run an interactive rebase
+        #[arg(short, long)]
+        interactive: bool,
+    },
+    /// Merge a branch into the current branch
+    Merge {
+        /// Branch to merge
+        branch: String,
+
+        /// Always create a merge commit
+        #[arg(long, conflicts_with = "ff_only")]
+        no_ff: bool,
+
+        /// Refuse to merge unless it can fast-forward
+        #[arg(long)]
+        ff_only: bool,
+
+        /// Push the current branch after a successful merge
+        #[arg(long)]
+        push: bool,
+    },
+    /// Apply a single commit onto the current branch
+    CherryPick {
+        /// Commit to cherry-pick
+        commit: String,
+
+        /// Apply the changes without creating a commit
+        #[arg(long)]
+        no_commit: bool,
+    },
+    /// Undo the last commit, keeping its changes staged
+    Undo {
+        /// Discard the changes as well (git reset --hard)
+        #[arg(long)]
+        hard: bool,
+    },
+    /// Reset the current branch to a commit
+    Reset {
+        /// Branch, tag or commit to reset to
+        target: String,
+
+        /// How much of the index and working tree to reset
+        #[arg(long, value_enum, default_value_t = ResetMode::Mixed)]
+        mode: ResetMode,
+    },
+    /// Show working tree or staged changes
+    Diff {
+        /// Show staged changes (git diff --cached)
+        #[arg(long)]
+        staged: bool,
+
+        /// Show a diffstat summary instead of the full diff
+        #[arg(long)]
+        stat: bool,
+
+        /// Never pipe output through a pager
+        #[arg(long)]
+        no_pager: bool,
+    },
+    /// Show commit history
+    Log {
+        /// Number of commits to show (default: 10)
+        #[arg(short = 'n', long)]
+        count: Option<usize>,
+
+        /// Show one commit per line
+        #[arg(long)]
+        oneline: bool,
+
+        /// Only show commits more recent than this date (e.g. "2 weeks ago")
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only show commits older than this date
+        #[arg(long)]
+        until: Option<String>,
+    },
+    /// Check the git install, repository, config and remote for common problems
+    Doctor {
+        /// Lowest check result that makes the command exit nonzero
+        #[arg(long, value_enum, default_value_t = CheckStatus::Fail)]
+        fail_on: CheckStatus,
+    },
+    /// Summarize commits, contributors and churn
+    Stats {
+        /// Only count commits more recent than this date
+        #[arg(long)]
+        since: Option<String>,
+    },
+    /// List contributors ranked by commit count
+    Contributors {
+        /// Only count commits more recent than this date
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only count commits older than this date
+        #[arg(long)]
+        until: Option<String>,
+    },
+    /// Show who last changed each line of a file
+    Blame {
+        file: PathBuf,
+
+        /// Line range to blame: START,END or START,+COUNT
+        #[arg(short = 'L', long, value_name = "RANGE")]
+        range: Option<String>,
+    },
+    /// Clone a repository
+    Clone {
+        url: String,
+
+        /// Destination directory (default: derived from the URL)
+        dest: Option<String>,
+
+        /// Write a default git-automate.toml into the new repository
+        #[arg(long)]
+        init_config: bool,
+    },
+    /// Initialize configuration
+    Init {
+        /// Where to write the config file
+        #[arg(long, default_value = "git-automate.toml")]
+        path: PathBuf,
+
+        /// Overwrite an existing config file
+        #[arg(short, long)]
+        force: bool,
+
+        /// Prompt for each setting instead of writing the defaults
+        #[arg(short, long)]
+        interactive: bool,
+    },
+    /// Print a shell completion script to stdout
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Show recent operations recorded in the operation log
+    History {
+        /// Number of entries to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        count: usize,
+    },
+    /// Show status
+    Status {
+        /// Print a compact one-line summary
+        #[arg(long)]
+        short: bool,
+    },
+}
+
+#[derive(Args, Clone, Default)]
+struct CommitArgs {
+    /// Custom commit message
+    #[arg(short, long)]
+    message: Option<String>,
+
+    /// Read the commit message from a file
+    #[arg(short = 'F', long, value_name = "PATH", conflicts_with = "message")]
+    message_file: Option<PathBuf>,
+
+    /// Files or pathspecs to add (default: all). Globs such as 'src/*.rs' are
+    /// matched by git itself, so quote them to stop the shell expanding them
+    #[arg(short, long)]
+    files: Option<Vec<String>>,
+
+    /// Stage only already-tracked files (git add -u), leaving untracked files out
+    #[arg(long, conflicts_with = "include_untracked")]
+    update_only: bool,
+
+    /// Stage untracked files as well (git add), overriding default_add_mode
+    #[arg(long)]
+    include_untracked: bool,
+
+    /// Pick hunks to stage interactively (git add -p) instead of staging whole files
+    #[arg(short, long, conflicts_with_all = ["update_only", "include_untracked"])]
+    patch: bool,
+
+    /// Show the staged diff and ask for confirmation before committing
+    #[arg(long)]
+    preview: bool,
+
+    /// Print the --preview diff directly instead of through a pager
+    #[arg(long, requires = "preview")]
+    no_pager: bool,
+
+    /// Use conventional commit format
+    #[arg(short, long)]
+    conventional: bool,
+
+    /// Amend the previous commit instead of creating a new one (re-signed when signing is enabled)
+    #[arg(long)]
+    amend: bool,
+
+    /// Commit onto another local branch, then switch back (best-effort: stops on conflicts)
+    #[arg(long, value_name = "BRANCH", conflicts_with_all = ["amend", "patch"])]
+    onto: Option<String>,
+
+    /// Write the commit message in your editor instead of generating one
+    #[arg(short, long, conflicts_with_all = ["message", "message_file", "amend"])]
+    edit: bool,
+
+    /// Force push using --force-with-lease
+    #[arg(long)]
+    force: bool,
+
+    /// Force push using a plain --force, overwriting remote changes
+    #[arg(long, conflicts_with = "force")]
+    force_unsafe: bool,
+
+    /// Allow committing to a protected branch
+    #[arg(long)]
+    allow_protected: bool,
+
+    /// Stage files even if they match commit_exclude
+    #[arg(long)]
+    allow_excluded: bool,
+
+    /// Skip pre-commit and commit-msg hooks
+    #[arg(long)]
+    no_verify: bool,
+
+    /// Skip the pre-push hook
+    #[arg(long)]
+    no_verify_push: bool,
+
+    /// Pull before committing even if auto_pull is disabled
+    #[arg(long, conflicts_with = "no_pull")]
+    pull: bool,
+
+    /// Don't pull before committing, overriding auto_pull
+    #[arg(long)]
+    no_pull: bool,
+
+    /// Push annotated tags that point at the pushed commits as well (git push --follow-tags)
+    #[arg(long)]
+    tags: bool,
+
+    /// Remote to push to and track (default: the configured remote)
+    #[arg(long, value_name = "REMOTE")]
+    upstream_remote: Option<String>,
+
+    /// Branch name to push to and track on the remote (default: the local branch name)
+    #[arg(long, value_name = "BRANCH")]
+    upstream_branch: Option<String>,
+
+    /// Add a Co-authored-by trailer (repeatable)
+    #[arg(long = "co-author", value_name = "NAME <EMAIL>")]
+    co_authors: Vec<String>,
+
+    /// Record a different author ("Name <email>"); the committer is still you
+    #[arg(long, value_name = "NAME <EMAIL>")]
+    author: Option<String>,
+
+    /// Add a Signed-off-by trailer for your git user.name/user.email, even if sign_off is off
+    #[arg(short = 's', long = "signoff")]
+    signoff: bool,
+
+    /// Create the commit even when there are no changes
+    #[arg(long)]
+    allow_empty: bool,
+
+    /// Fail with exit code 3 when there is nothing to commit
+    #[arg(long, conflicts_with = "allow_empty")]
+    require_changes: bool,
+
+    /// Skip the configured pre_commit_command
+    #[arg(long)]
+    skip_checks: bool,
+
+    /// Skip the commit message checks (blank second line, max_subject_length,
+    /// forbid_subject_period and require_conventional)
+    #[arg(long)]
+    no_lint: bool,
+
+    /// Automatically abort the merge or rebase if pulling hits conflicts
+    #[arg(long)]
+    abort_on_conflict: bool,
+
+    /// Conventional commit type (default: feat)
+    #[arg(short = 't', long = "type", value_name = "TYPE")]
+    commit_type: Option<String>,
+
+    /// Conventional commit scope
+    #[arg(long)]
+    scope: Option<String>,
+
+    /// Mark the commit as a breaking change (adds `!` after the type/scope)
+    #[arg(long)]
+    breaking: bool,
+
+    /// Sign the commit, overriding sign_commits from config; with --amend the amended commit is signed
+    #[arg(long, overrides_with = "no_sign")]
+    sign: bool,
+
+    /// Do not sign the commit, overriding sign_commits from config
+    #[arg(long, overrides_with = "sign")]
+    no_sign: bool,
+}
+
+impl Commands {
+    fn requires_repo(&self) -> bool {
+        match self {
+            Commands::Clone { .. } | Commands::Init { .. } | Commands::Completions { .. } | Commands::Doctor { .. } => {
+                false
+            }
+            Commands::GitConfig { cmd } => !cmd.global(),
+            _ => true,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum BranchCommands {
+    /// Create a new branch
+    Create {
+        name: String,
+
+        /// Don't prepend the configured branch_prefix
+        #[arg(long)]
+        no_prefix: bool,
+
+        /// Branch off this ref instead of HEAD; a remote branch (e.g. origin/main) is also tracked
+        #[arg(long, value_name = "BASE")]
+        from: Option<String>,
+
+        /// Commit type to file the branch under, after any branch_prefix:
+        /// `--type fix "login timeout"` creates fix/login-timeout
+        #[arg(short = 't', long = "type", value_name = "TYPE")]
+        branch_type: Option<String>,
+
+        /// With --type, keep the name as given instead of slugifying it
+        #[arg(long, requires = "branch_type")]
+        raw: bool,
+    },
+    /// Switch to a branch (prompts for one when no name is given)
+    Switch {
+        name: Option<String>,
+
+        /// Stash uncommitted changes, switch, then re-apply them
+        #[arg(long)]
+        stash: bool,
+    },
+    /// Delete a branch
+    Delete {
+        name: String,
+
+        /// Delete even if the branch is not fully merged (git branch -D)
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Rename a branch (the current one when only the new name is given)
+    #[command(allow_missing_positional = true)]
+    Rename {
+        old: Option<String>,
+        new: String,
+
+        /// Push the renamed branch and set its upstream if the old one had one
+        #[arg(long)]
+        push: bool,
+    },
+    /// List branches
+    List {
+        /// Include remote-tracking branches
+        #[arg(short, long)]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum BisectCommands {
+    /// Start bisecting between a known good and a known bad commit
+    Start { good: String, bad: String },
+    /// Mark a commit (default: the checked-out one) as good
+    Good { commit: Option<String> },
+    /// Mark a commit (default: the checked-out one) as bad
+    Bad { commit: Option<String> },
+    /// Stop bisecting and return to the original branch
+    Reset,
+    /// Let a test command mark commits automatically (exit 0 = good, 1-127 except 125 = bad)
+    Run { command: String },
+}
+
+#[derive(Subcommand)]
+enum GitConfigCommands {
+    /// Print the value of a key
+    Get {
+        key: String,
+
+        /// Read from the global (~/.gitconfig) scope
+        #[arg(long)]
+        global: bool,
+    },
+    /// Set a key
+    Set {
+        key: String,
+        value: String,
+
+        /// Write to the global (~/.gitconfig) scope instead of this repository
+        #[arg(long)]
+        global: bool,
+    },
+}
+
+impl BranchCommands {
+    fn action(&self) -> &'static str {
+        match self {
+            BranchCommands::Create { .. } => "create",
+            BranchCommands::Switch { .. } => "switch",
+            BranchCommands::Delete { .. } => "delete",
+            BranchCommands::Rename { .. } => "rename",
+            BranchCommands::List { .. } => "list",
+        }
+    }
+}
+
+impl GitConfigCommands {
+    fn global(&self) -> bool {
+        match self {
+            GitConfigCommands::Get { global, .. } | GitConfigCommands::Set { global, .. } => *global,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum TagCommands {
+    /// Create a tag (annotated when a message is given)
+    Create {
+        name: String,
+
+        /// Tag message; creates an annotated tag
+        #[arg(short, long)]
+        message: Option<String>,
+
+        /// Push the tag to the default remote
+        #[arg(long)]
+        push: bool,
+
+        /// Sign the tag, overriding sign_tags from config (requires a message)
+        #[arg(long, conflicts_with = "no_sign")]
+        sign: bool,
+
+        /// Do not sign the tag, overriding sign_tags from config
+        #[arg(long)]
+        no_sign: bool,
+    },
+    /// Delete a local tag
+    Delete { name: String },
+    /// List tags
+    List,
+}
+
+#[derive(Subcommand)]
+enum WorktreeCommands {
+    /// Check out a branch into a new worktree, creating the branch if needed
+    Add { path: PathBuf, branch: String },
+    /// List worktrees
+    List,
+    /// Remove a worktree (refuses if it has uncommitted changes)
+    Remove {
+        path: PathBuf,
+
+        /// Remove even if the worktree has uncommitted changes
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum RemoteCommands {
+    /// List configured remotes
+    List,
+}
+
+#[derive(Subcommand)]
+enum StashCommands {
+    /// Stash uncommitted changes
+    Save {
+        /// Stash message
         #[arg(short, long)]
         message: Option<String>,
+    },
+    /// Apply and remove the most recent stash
+    Pop,
+    /// List stash entries
+    List,
+    /// Drop a stash entry (default: most recent)
+    Drop { index: Option<usize> },
+}
+
+struct CommitOptions {
+    no_verify: bool,
+    sign: bool,
+    allow_empty: bool,
+    author: Option<String>,
+    /// The `Name <email>` git will sign off with; `None` means no sign-off.
+    sign_off: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+enum ResetMode {
+    Soft,
+    Mixed,
+    Hard,
+}
+
+impl ResetMode {
+    fn flag(self) -> &'static str {
+        match self {
+            ResetMode::Soft => "--soft",
+            ResetMode::Mixed => "--mixed",
+            ResetMode::Hard => "--hard",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ForceMode {
+    None,
+    WithLease,
+    Unsafe,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum FileState {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Copied,
+    TypeChanged,
+    Unmerged,
+    Untracked,
+    Ignored,
+}
+
+impl FileState {
+    fn from_code(code: char) -> Option<Self> {
+        match code {
+            'A' => Some(Self::Added),
+            'M' => Some(Self::Modified),
+            'D' => Some(Self::Deleted),
+            'R' => Some(Self::Renamed),
+            'C' => Some(Self::Copied),
+            'T' => Some(Self::TypeChanged),
+            'U' => Some(Self::Unmerged),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Modified => "modified",
+            Self::Deleted => "deleted",
+            Self::Renamed => "renamed",
+            Self::Copied => "copied",
+            Self::TypeChanged => "typechange",
+            Self::Unmerged => "unmerged",
+            Self::Untracked => "untracked",
+            Self::Ignored => "ignored",
+        }
+    }
+}
+
+#[derive(Default, Serialize)]
+struct RepoStats {
+    commits: usize,
+    contributors: usize,
+    lines_added: u64,
+    lines_removed: u64,
+    most_changed_files: Vec<FileChurn>,
+}
+
+#[derive(Serialize)]
+struct FileChurn {
+    path: String,
+    commits: usize,
+    lines_changed: u64,
+}
+
+#[derive(Serialize)]
+struct BlameLine {
+    line: usize,
+    author: String,
+    sha: String,
+    date: String,
+}
+
+#[derive(Clone, Serialize)]
+struct StatusEntry {
+    path: String,
+    original_path: Option<String>,
+    staged: Option<FileState>,
+    unstaged: Option<FileState>,
+}
+
+impl StatusEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let mut chars = line.chars();
+        let (x, y) = (chars.next()?, chars.next()?);
+        let rest = line.get(3..)?;
+
+        let (original_path, path) = match rest.split_once(" -> ") {
+            Some((from, to)) if matches!(x, 'R' | 'C') => (Some(unquote_path(from)), unquote_path(to)),
+            _ => (None, unquote_path(rest)),
+        };
+
+        let (staged, unstaged) = match (x, y) {
+            ('?', '?') => (None, Some(FileState::Untracked)),
+            ('!', '!') => (None, Some(FileState::Ignored)),
+            ('D', 'D') | ('A', 'A') | ('U', _) | (_, 'U') => (Some(FileState::Unmerged), None),
+            (x, y) => (FileState::from_code(x), FileState::from_code(y)),
+        };
+
+        Some(Self { path, original_path, staged, unstaged })
+    }
+
+    fn display_path(&self) -> String {
+        match &self.original_path {
+            Some(original) => format!("{} -> {}", original, self.path),
+            None => self.path.clone(),
+        }
+    }
+}
+
+/// Undoes git's C-style quoting of unusual paths, including octal escapes for
+/// non-ASCII bytes (`core.quotePath`).
+fn unquote_path(path: &str) -> String {
+    let Some(quoted) = path.strip_prefix('"').and_then(|p| p.strip_suffix('"')) else {
+        return path.to_string();
+    };
+
+    let mut bytes = Vec::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('r') => bytes.push(b'\r'),
+            Some('a') => bytes.push(0x07),
+            Some('b') => bytes.push(0x08),
+            Some('f') => bytes.push(0x0c),
+            Some('v') => bytes.push(0x0b),
+            Some(digit @ '0'..='7') => {
+                let octal: String = std::iter::once(digit).chain(chars.by_ref().take(2)).collect();
+                bytes.push(u8::from_str_radix(&octal, 8).unwrap_or(b'?'));
+            }
+            Some(other) => bytes.extend_from_slice(other.encode_utf8(&mut [0; 4]).as_bytes()),
+            None => bytes.push(b'\\'),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[derive(Default, Serialize)]
+struct UpstreamStatus {
+    upstream: Option<String>,
+    ahead: usize,
+    behind: usize,
+}
+
+impl fmt::Display for UpstreamStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.upstream, self.ahead, self.behind) {
+            (None, _, _) => write!(f, "no upstream configured"),
+            (Some(_), 0, 0) => write!(f, "up to date"),
+            (Some(_), ahead, 0) => write!(f, "ahead {}", ahead),
+            (Some(_), 0, behind) => write!(f, "behind {}", behind),
+            (Some(_), ahead, behind) => write!(f, "ahead {}, behind {}", ahead, behind),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+enum CheckStatus {
+    #[value(skip)]
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Serialize)]
+struct DoctorCheck {
+    name: &'static str,
+    status: CheckStatus,
+    detail: String,
+    hint: Option<String>,
+}
+
+impl DoctorCheck {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Pass, detail: detail.into(), hint: None }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Warn, detail: detail.into(), hint: Some(hint.into()) }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Fail, detail: detail.into(), hint: Some(hint.into()) }
+    }
+}
+
+#[derive(Serialize)]
+struct DoctorReport<'a> {
+    ok: bool,
+    checks: &'a [DoctorCheck],
+}
+
+/// Printed by branch create/switch/delete/rename under `--format json`.
+#[derive(Serialize)]
+struct BranchOpResult {
+    action: &'static str,
+    branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Default, Serialize)]
+struct Worktree {
+    path: String,
+    head: Option<String>,
+    /// `None` for a detached or bare worktree.
+    branch: Option<String>,
+    bare: bool,
+    locked: bool,
+    prunable: bool,
+}
+
+impl Worktree {
+    /// Parses `git worktree list --porcelain`: one blank-line separated block per worktree.
+    fn parse_porcelain(output: &str) -> Vec<Worktree> {
+        let mut worktrees = Vec::new();
+        for block in output.split("\n\n") {
+            let mut worktree = Worktree::default();
+            for line in block.lines() {
+                let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+                match key {
+                    "worktree" => worktree.path = value.to_string(),
+                    "HEAD" => worktree.head = Some(value.to_string()),
+                    "branch" => worktree.branch = Some(value.trim_start_matches("refs/heads/").to_string()),
+                    "bare" => worktree.bare = true,
+                    "locked" => worktree.locked = true,
+                    "prunable" => worktree.prunable = true,
+                    _ => {}
+                }
+            }
+            if !worktree.path.is_empty() {
+                worktrees.push(worktree);
+            }
+        }
+        worktrees
+    }
+}
+
+#[derive(Serialize)]
+struct BranchInfo {
+    name: String,
+    current: bool,
+    /// Remote-tracking refs listed with `--all` are not themselves tracking anything.
+    #[serde(skip)]
+    remote: bool,
+    /// The upstream is configured but no longer exists on the remote.
+    gone: bool,
+    #[serde(flatten)]
+    tracking: UpstreamStatus,
+}
+
+impl fmt::Display for BranchInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.tracking.upstream {
+            _ if self.remote => Ok(()),
+            None => write!(f, "[no upstream]"),
+            Some(upstream) if self.gone => write!(f, "[{}: gone]", upstream),
+            Some(upstream) if self.tracking.ahead == 0 && self.tracking.behind == 0 => write!(f, "[{}]", upstream),
+            Some(upstream) => write!(f, "[{}: {}]", upstream, self.tracking),
+        }
+    }
+}
+
+struct StatusSnapshot {
+    branch: String,
+    has_changes: bool,
+    entries: Vec<StatusEntry>,
+    upstream: UpstreamStatus,
+}
+
+#[derive(Serialize)]
+struct StatusReport {
+    branch: String,
+    has_changes: bool,
+    upstream: Option<String>,
+    ahead: Option<usize>,
+    behind: Option<usize>,
+    entries: Vec<StatusEntry>,
+}
+
+const CONFIG_VERSION: u32 = 2;
+
+fn legacy_config_version() -> u32 {
+    1
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct Config {
+    /// Files written before versioning was introduced have no `version` key and count as v1.
+    #[serde(default = "legacy_config_version")]
+    version: u32,
+    default_remote: String,
+    commit_template: String,
+    auto_pull: bool,
+    protected_branches: Vec<String>,
+    pull_strategy: String,
+    network_retries: u32,
+    extra_commit_types: Vec<String>,
+    sign_commits: bool,
+    signing_key: Option<String>,
+    /// Follows sign_commits when unset.
+    sign_tags: Option<bool>,
+    git_path: Option<String>,
+    pre_commit_command: Option<String>,
+    empty_commit_behavior: String,
+    command_timeout_secs: Option<u64>,
+    default_add_mode: String,
+    wip_prefix: String,
+    co_authors: Vec<String>,
+    ticket_pattern: Option<String>,
+    require_ticket: bool,
+    ticket_placement: String,
+    post_push_command: Option<String>,
+    post_push_fatal: bool,
+    /// Prepended verbatim to new branch names, e.g. "{user}/".
+    branch_prefix: Option<String>,
+    interactive_message: bool,
+    /// Describe the staged changes instead of using a random name when no message is given.
+    message_from_diff: bool,
+    max_subject_length: Option<usize>,
+    /// Reject subjects ending in a period.
+    forbid_subject_period: bool,
+    require_conventional: bool,
+    push_tags: bool,
+    /// Add a Signed-off-by trailer (git commit --signoff) to every commit.
+    sign_off: bool,
+    /// Glob patterns for files that must not be staged, e.g. ".env" or "dist/**".
+    commit_exclude: Vec<String>,
+    commit_exclude_action: String,
+    #[serde(flatten, skip_serializing)]
+    unknown_keys: BTreeMap<String, toml::Value>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            default_remote: String::from("origin"),
+            commit_template: String::from("feat: {}"),
+            auto_pull: true,
+            protected_branches: vec![String::from("main"), String::from("master")],
+            pull_strategy: String::from("merge"),
+            network_retries: 0,
+            extra_commit_types: Vec::new(),
+            sign_commits: false,
+            signing_key: None,
+            sign_tags: None,
+            git_path: None,
+            pre_commit_command: None,
+            empty_commit_behavior: String::from("skip"),
+            command_timeout_secs: None,
+            default_add_mode: String::from("all"),
+            wip_prefix: String::from("wip"),
+            co_authors: Vec::new(),
+            ticket_pattern: None,
+            require_ticket: false,
+            ticket_placement: String::from("trailer"),
+            post_push_command: None,
+            post_push_fatal: false,
+            branch_prefix: None,
+            interactive_message: false,
+            message_from_diff: false,
+            max_subject_length: None,
+            forbid_subject_period: false,
+            require_conventional: false,
+            push_tags: false,
+            sign_off: false,
+            commit_exclude: Vec::new(),
+            commit_exclude_action: String::from("abort"),
+            unknown_keys: BTreeMap::new(),
+        }
+    }
+}
+
+const PULL_STRATEGIES: &[&str] = &["merge", "rebase", "ff-only"];
+const EMPTY_COMMIT_BEHAVIORS: &[&str] = &["skip", "error", "allow-empty"];
+const ADD_MODES: &[&str] = &["all", "update"];
+const TICKET_PLACEMENTS: &[&str] = &["trailer", "prefix", "suffix"];
+const EXCLUDE_ACTIONS: &[&str] = &["abort", "warn"];
+
+impl Config {
+    /// Upgrades an older config in memory. Returns whether anything changed.
+    fn migrate(&mut self) -> bool {
+        if self.version >= CONFIG_VERSION {
+            return false;
+        }
+        // v1 -> v2 only adds the version key; every newer field already has a serde default.
+        self.version = CONFIG_VERSION;
+        true
+    }
+
+    fn apply_env_overrides(&mut self) -> Result<()> {
+        self.apply_overrides(|name| std::env::var(name).ok())
+    }
+
+    /// Applies the `GIT_AUTOMATE_*` overrides that `lookup` finds; split out so
+    /// tests can supply variables without touching the process environment.
+    fn apply_overrides(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<()> {
+        if let Some(remote) = lookup("GIT_AUTOMATE_REMOTE") {
+            self.default_remote = remote;
+        }
+        if let Some(auto_pull) = lookup("GIT_AUTOMATE_AUTO_PULL") {
+            self.auto_pull = parse_env_bool("GIT_AUTOMATE_AUTO_PULL", &auto_pull)?;
+        }
+        if let Some(template) = lookup("GIT_AUTOMATE_COMMIT_TEMPLATE") {
+            self.commit_template = template;
+        }
+        if let Some(git_path) = lookup("GIT_AUTOMATE_GIT_PATH") {
+            self.git_path = Some(git_path).filter(|path| !path.is_empty());
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        if self.version > CONFIG_VERSION {
+            problems.push(format!(
+                "version {} is newer than this git-automate supports (max {})",
+                self.version, CONFIG_VERSION
+            ));
+        }
+        if self.default_remote.trim().is_empty() {
+            problems.push(format!("default_remote must not be empty (got {:?})", self.default_remote));
+        }
+        if !self.commit_template.contains("{}") {
+            problems.push(format!(
+                "commit_template must contain a {{}} placeholder (got {:?})",
+                self.commit_template
+            ));
+        }
+        if !PULL_STRATEGIES.contains(&self.pull_strategy.as_str()) {
+            problems.push(format!(
+                "pull_strategy must be one of {} (got {:?})",
+                PULL_STRATEGIES.join(", "),
+                self.pull_strategy
+            ));
+        }
+        if !ADD_MODES.contains(&self.default_add_mode.as_str()) {
+            problems.push(format!(
+                "default_add_mode must be one of {} (got {:?})",
+                ADD_MODES.join(", "),
+                self.default_add_mode
+            ));
+        }
+        for co_author in &self.co_authors {
+            if let Err(e) = validate_identity("co-author", co_author) {
+                problems.push(format!("co_authors: {}", e));
+            }
+        }
+        if let Some(pattern) = &self.ticket_pattern {
+            if let Err(e) = Regex::new(pattern) {
+                problems.push(format!("ticket_pattern is not a valid regex ({:?}): {}", pattern, e));
+            }
+        } else if self.require_ticket {
+            problems.push(String::from("require_ticket is set but ticket_pattern is not"));
+        }
+        if !TICKET_PLACEMENTS.contains(&self.ticket_placement.as_str()) {
+            problems.push(format!(
+                "ticket_placement must be one of {} (got {:?})",
+                TICKET_PLACEMENTS.join(", "),
+                self.ticket_placement
+            ));
+        }
+        if !EXCLUDE_ACTIONS.contains(&self.commit_exclude_action.as_str()) {
+            problems.push(format!(
+                "commit_exclude_action must be one of {} (got {:?})",
+                EXCLUDE_ACTIONS.join(", "),
+                self.commit_exclude_action
+            ));
+        }
+        if self.commit_exclude.iter().any(|pattern| pattern.trim().is_empty()) {
+            problems.push(String::from("commit_exclude must not contain empty patterns"));
+        }
+        if self.wip_prefix.trim().is_empty() {
+            problems.push(String::from("wip_prefix must not be empty"));
+        }
+        if self.max_subject_length == Some(0) {
+            problems.push(String::from("max_subject_length must be greater than 0"));
+        }
+        if self.command_timeout_secs == Some(0) {
+            problems.push(String::from("command_timeout_secs must be greater than 0"));
+        }
+        if !EMPTY_COMMIT_BEHAVIORS.contains(&self.empty_commit_behavior.as_str()) {
+            problems.push(format!(
+                "empty_commit_behavior must be one of {} (got {:?})",
+                EMPTY_COMMIT_BEHAVIORS.join(", "),
+                self.empty_commit_behavior
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("\n  - {}", problems.join("\n  - ")))
+        }
+    }
+}
+
+/// A single operation recorded by `--dry-run --format json` instead of being run.
+#[derive(Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum PlanStep {
+    Pull { remote: Option<String>, strategy: String },
+    Fetch { args: Vec<String> },
+    Rebase { onto: String, interactive: bool },
+    CherryPick { commit: String, no_commit: bool },
+    Merge { branch: String, no_ff: bool, ff_only: bool },
+    Check { command: String },
+    Add { files: Vec<String>, update_only: bool },
+    AddPatch { files: Vec<String> },
+    Commit { message: String, no_verify: bool, sign: bool, allow_empty: bool, author: Option<String> },
+    CommitInEditor { trailers: Vec<String>, no_verify: bool, sign: bool, allow_empty: bool, author: Option<String> },
+    Amend { message: Option<String>, no_verify: bool, sign: bool, author: Option<String> },
+    Push {
+        remote: String,
+        branch: String,
+        upstream_branch: Option<String>,
+        force: ForceMode,
+        set_upstream: bool,
+        no_verify: bool,
+        follow_tags: bool,
+    },
+    CreateBranch { name: String, from: Option<String>, track: bool },
+    SwitchBranch { name: String },
+    DeleteBranch { name: String, force: bool },
+    RenameBranch { old: String, new: String },
+    CreateTag { name: String, message: Option<String>, sign: bool },
+    SetGitConfig { key: String, value: String, global: bool },
+    BisectStart { good: String, bad: String },
+    BisectMark { term: String, commit: Option<String> },
+    BisectRun { command: String },
+    BisectReset,
+    PushTag { remote: String, name: String },
+    DeleteTag { name: String },
+    WorktreeAdd { path: String, branch: String, create_branch: bool },
+    WorktreeRemove { path: String, force: bool },
+    Reset { mode: ResetMode, target: String },
+    Clone { url: String, dest: String },
+    StashSave { message: Option<String> },
+    StashPop,
+    StashDrop { stash: Option<String> },
+    Clean { paths: Vec<String> },
+    Restore { files: Vec<String>, staged: bool },
+    RemotePrune { remote: String },
+    PullRequest { args: Vec<String> },
+}
+
+struct GitOutput {
+    success: bool,
+    stdout: String,
+    stderr: String,
+}
+
+struct GitOps {
+    config: Config,
+    dry_run: bool,
+    remote_override: Option<String>,
+    stream: bool,
+    assume_yes: bool,
+    // Thread-safe so read-only queries can run concurrently (see `status_snapshot`).
+    plan: Option<Mutex<Vec<PlanStep>>>,
+    operation_log: OnceLock<Option<PathBuf>>,
+    remote_confirmed: OnceLock<bool>,
+}
+
+impl GitOps {
+    fn new(
+        config: Config,
+        dry_run: bool,
+        remote_override: Option<String>,
+        stream: bool,
+        assume_yes: bool,
+        record_plan: bool,
+    ) -> Self {
+        let plan = (dry_run && record_plan).then(|| Mutex::new(Vec::new()));
+        Self {
+            config,
+            dry_run,
+            remote_override,
+            stream,
+            assume_yes,
+            plan,
+            operation_log: OnceLock::new(),
+            remote_confirmed: OnceLock::new(),
+        }
+    }
+
+    fn record(&self, step: PlanStep) {
+        if let Some(plan) = &self.plan {
+            plan.lock().unwrap_or_else(PoisonError::into_inner).push(step);
+        }
+    }
+
+    fn take_plan(&self) -> Vec<PlanStep> {
+        self.plan
+            .as_ref()
+            .map(|plan| std::mem::take(&mut *plan.lock().unwrap_or_else(PoisonError::into_inner)))
+            .unwrap_or_default()
+    }
+
+    fn git(&self) -> &str {
+        self.config.git_path.as_deref().unwrap_or("git")
+    }
+
+    fn check_git_executable(&self) -> Result<()> {
+        match Command::new(self.git()).arg("--version").output() {
+            Ok(output) => {
+                info!("Using {} ({})", String::from_utf8_lossy(&output.stdout).trim(), self.git());
+                Ok(())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Err(GitError::GitNotFound { path: self.config.git_path.clone() }.into())
+            }
+            Err(e) => Err(anyhow!("Failed to run git executable {}: {}", self.git(), e)),
+        }
+    }
+
+    fn git_command(&self) -> Command {
+        let mut command = Command::new(self.git());
+        // Fail credential prompts immediately instead of hanging on a terminal nobody is watching.
+        command.env("GIT_TERMINAL_PROMPT", "0");
+        command
+    }
+
+    fn git_output<S: AsRef<OsStr>>(&self, args: &[S], streamed: bool) -> io::Result<GitOutput> {
+        let mut child = self
+            .git_command()
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(if streamed { Stdio::inherit() } else { Stdio::piped() })
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let stdout_reader = child.stdout.take().map(|mut child_stdout| {
+            thread::spawn(move || -> io::Result<Vec<u8>> {
+                let mut captured = Vec::new();
+                child_stdout.read_to_end(&mut captured)?;
+                Ok(captured)
+            })
+        });
+        let stderr_reader = child.stderr.take().map(|mut child_stderr| {
+            thread::spawn(move || -> io::Result<Vec<u8>> {
+                let mut captured = Vec::new();
+                let mut buffer = [0u8; 4096];
+                loop {
+                    let read = child_stderr.read(&mut buffer)?;
+                    if read == 0 {
+                        break;
+                    }
+                    if streamed {
+                        io::stderr().write_all(&buffer[..read])?;
+                    }
+                    captured.extend_from_slice(&buffer[..read]);
+                }
+                Ok(captured)
+            })
+        });
+
+        let status = self.wait_with_timeout(&mut child)?;
+        self.log_operation(args, status);
+
+        let join = |reader: Option<thread::JoinHandle<io::Result<Vec<u8>>>>| -> io::Result<String> {
+            let captured = match reader {
+                Some(reader) => reader.join().map_err(|_| io::Error::other("git output reader panicked"))??,
+                None => Vec::new(),
+            };
+            Ok(String::from_utf8_lossy(&captured).into_owned())
+        };
+
+        Ok(GitOutput {
+            success: status.success(),
+            stdout: join(stdout_reader)?,
+            stderr: join(stderr_reader)?,
+        })
+    }
+
+    /// Like `run_git`, but hands stdout to `on_line` one line at a time instead of
+    /// collecting it, for output too large to hold. `--timeout` still applies.
+    fn git_lines<S: AsRef<OsStr>>(
+        &self,
+        args: &[S],
+        action: &str,
+        mut on_line: impl FnMut(&str) + Send,
+    ) -> Result<()> {
+        let mut child = self
+            .git_command()
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| self.spawn_error(&format!("{} failed: could not run git", action), e))?;
+        let (Some(child_stdout), Some(mut child_stderr)) = (child.stdout.take(), child.stderr.take()) else {
+            return Err(anyhow!("{} failed: could not read git output", action));
+        };
+
+        let (status, read, stderr) = thread::scope(|scope| {
+            let lines = scope.spawn(move || -> io::Result<()> {
+                let mut reader = BufReader::new(child_stdout);
+                let mut line = Vec::new();
+                while reader.read_until(b'\n', &mut line)? > 0 {
+                    let text = String::from_utf8_lossy(&line);
+                    on_line(text.trim_end_matches(['\n', '\r']));
+                    line.clear();
+                }
+                Ok(())
+            });
+            let errors = scope.spawn(move || -> io::Result<String> {
+                let mut captured = String::new();
+                child_stderr.read_to_string(&mut captured)?;
+                Ok(captured)
+            });
+            // Killing the child on timeout closes both pipes, so the readers finish too.
+            let status = self.wait_with_timeout(&mut child);
+            let panicked = |_| io::Error::other("git output reader panicked");
+            let read = lines.join().map_err(panicked).and_then(|read| read);
+            let stderr = errors.join().map_err(panicked).and_then(|stderr| stderr);
+            (status, read, stderr)
+        });
+
+        let status = status.map_err(|e| self.spawn_error(&format!("{} failed", action), e))?;
+        self.log_operation(args, status);
+        read.map_err(|e| anyhow!("{} failed: could not read git output: {}", action, e))?;
+        let stderr = stderr.unwrap_or_default();
+        if !status.success() {
+            error!("{} failed: {}", action, stderr);
+            return Err(GitError::CommandFailed {
+                action: action.to_string(),
+                stderr: stderr.trim_end().to_string(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    fn wait_with_timeout(&self, child: &mut Child) -> io::Result<ExitStatus> {
+        let Some(secs) = self.config.command_timeout_secs else {
+            return child.wait();
+        };
+
+        let deadline = Instant::now() + Duration::from_secs(secs);
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(status);
+            }
+            if Instant::now() >= deadline {
+                child.kill()?;
+                child.wait()?;
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("git command timed out after {}s", secs),
+                ));
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    fn operation_log_path(&self) -> Option<&Path> {
+        self.operation_log
+            .get_or_init(|| {
+                let output = self.git_command().args(["rev-parse", "--git-dir"]).output().ok()?;
+                if !output.status.success() {
+                    return None;
+                }
+                let git_dir = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+                Some(git_dir.join(OPERATION_LOG_FILE))
+            })
+            .as_deref()
+    }
+
+    /// Appends a mutating git invocation to the operation log. Failures to write
+    /// the log are reported but never fail the operation itself.
+    fn log_operation<S: AsRef<OsStr>>(&self, args: &[S], status: ExitStatus) {
+        if is_query_command(args) {
+            return;
+        }
+        let Some(path) = self.operation_log_path() else {
+            return;
+        };
+
+        let command = args
+            .iter()
+            .map(|arg| {
+                let arg = arg.as_ref().to_string_lossy();
+                if arg.is_empty() || arg.contains(char::is_whitespace) { format!("{:?}", arg) } else { arg.into_owned() }
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let exit = status.code().map(|code| code.to_string()).unwrap_or_else(|| String::from("signal"));
+        let line = format!("{}\texit={}\tgit {}\n", chrono::Local::now().to_rfc3339(), exit, command);
+
+        if let Err(e) = append_operation_log(path, &line) {
+            warn!("Failed to write operation log {}: {}", path.display(), e);
+        }
+    }
+
+    fn read_history(&self, count: usize) -> Result<Vec<String>> {
+        let Some(path) = self.operation_log_path() else {
+            return Ok(Vec::new());
+        };
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let contents = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read operation log {}: {}", path.display(), e))?;
+        let lines: Vec<String> = contents.lines().map(String::from).collect();
+        Ok(lines[lines.len().saturating_sub(count)..].to_vec())
+    }
+
+    /// Runs git and returns its stdout with trailing whitespace removed, or an
+    /// "<action> failed: <stderr>" error. Leading whitespace is kept because it
+    /// is significant in porcelain and `--stat` output.
+    fn run_git<S: AsRef<OsStr>>(&self, args: &[S], action: &str) -> Result<String> {
+        let output = self
+            .git_output(args, false)
+            .map_err(|e| self.spawn_error(&format!("{} failed: could not run git", action), e))?;
+
+        if !output.success {
+            error!("{} failed: {}", action, output.stderr);
+            return Err(GitError::CommandFailed {
+                action: action.to_string(),
+                stderr: output.stderr.trim_end().to_string(),
+            }
+            .into());
+        }
+
+        Ok(output.stdout.trim_end().to_string())
+    }
+
+    /// Turns a failure to run git into an error, keeping timeouts as `GitError::Timeout`.
+    fn spawn_error(&self, context: &str, e: io::Error) -> anyhow::Error {
+        match (e.kind(), self.config.command_timeout_secs) {
+            (io::ErrorKind::TimedOut, Some(secs)) => {
+                anyhow::Error::from(GitError::Timeout { secs }).context(context.to_string())
+            }
+            _ => anyhow!("{}: {}", context, e),
+        }
+    }
+
+    fn git_succeeds<S: AsRef<OsStr>>(&self, args: &[S]) -> bool {
+        self.git_output(args, false).map(|output| output.success).unwrap_or(false)
+    }
+
+    fn remote(&self) -> &str {
+        self.remote_override.as_deref().unwrap_or(&self.config.default_remote)
+    }
+
+    fn get_current_branch(&self) -> Result<String> {
+        // symbolic-ref also works on an unborn branch, where rev-parse HEAD fails.
+        // Failing here just means HEAD is detached, so probe quietly instead of through run_git.
+        if let Ok(output) = self.git_output(&["symbolic-ref", "--short", "-q", "HEAD"], false) {
+            if output.success {
+                return Ok(output.stdout.trim_end().to_string());
+            }
+        }
+
+        let branch = self.run_git(&["rev-parse", "--abbrev-ref", "HEAD"], "Current branch lookup")?;
+        if branch == "HEAD" {
+            error!("Repository is in detached HEAD state");
+            return Err(anyhow!("Repository is in detached HEAD state; check out a branch first"));
+        }
+
+        Ok(branch)
+    }
+
+    fn check_git_repo(&self) -> bool {
+        self.git_succeeds(&["rev-parse", "--is-inside-work-tree"])
+    }
+
+    fn has_changes(&self) -> Result<bool> {
+        Ok(!self.run_git(&["status", "--porcelain"], "Status")?.is_empty())
+    }
+
+    fn has_tracked_changes(&self) -> Result<bool> {
+        Ok(!self.run_git(&["status", "--porcelain", "--untracked-files=no"], "Status")?.is_empty())
+    }
+
+    /// Fingerprints the working tree for `watch`: the diff of tracked files plus
+    /// the size and mtime of each untracked file, or `None` when it is clean.
+    fn watch_snapshot(&self) -> Result<Option<u64>> {
+        let diff = if self.git_succeeds(&["rev-parse", "--verify", "--quiet", "HEAD"]) {
+            self.run_git(&["diff", "HEAD", "--binary", "--no-ext-diff"], "Diff")?
+        } else {
+            // Nothing committed yet: the index against the empty tree, then the work tree against the index.
+            let staged = self.run_git(&["diff", "--cached", "--binary", "--no-ext-diff"], "Diff")?;
+            let unstaged = self.run_git(&["diff", "--binary", "--no-ext-diff"], "Diff")?;
+            format!("{}\n{}", staged, unstaged)
+        };
+        let untracked = self.run_git(&["ls-files", "--others", "--exclude-standard", "-z"], "List untracked files")?;
+        if diff.trim().is_empty() && untracked.is_empty() {
+            return Ok(None);
+        }
+
+        let mut hasher = DefaultHasher::new();
+        diff.hash(&mut hasher);
+        for path in untracked.split('\0').filter(|path| !path.is_empty()) {
+            path.hash(&mut hasher);
+            if let Ok(metadata) = fs::metadata(path) {
+                metadata.len().hash(&mut hasher);
+                metadata.modified().ok().hash(&mut hasher);
+            }
+        }
+        Ok(Some(hasher.finish()))
+    }
+
+    fn staged_diff_stat(&self) -> Result<String> {
+        // A wide name column keeps git from abbreviating long paths with "...".
+        self.run_git(&["diff", "--cached", "--stat=1000,1000"], "Staged diff")
+    }
+
+    fn has_staged_changes(&self) -> Result<bool> {
+        Ok(!self.git_succeeds(&["diff", "--cached", "--quiet"]))
+    }
+
+    fn status_entries(&self) -> Result<Vec<StatusEntry>> {
+        Ok(self
+            .run_git(&["status", "--porcelain=v1"], "Status")?
+            .lines()
+            .filter_map(StatusEntry::parse)
+            .collect())
+    }
+
+    fn upstream_status(&self) -> Result<UpstreamStatus> {
+        let output = self
+            .git_output(&["rev-parse", "--abbrev-ref", "--symbolic-full-name", "@{upstream}"], false)
+            .map_err(|e| self.spawn_error("Failed to look up upstream", e))?;
+        if !output.success {
+            return Ok(UpstreamStatus::default());
+        }
+        let upstream = output.stdout.trim().to_string();
+
+        // With no local commits yet there is nothing to compare.
+        if !self.git_succeeds(&["rev-parse", "--verify", "--quiet", "HEAD"]) {
+            return Ok(UpstreamStatus { upstream: Some(upstream), ..UpstreamStatus::default() });
+        }
+
+        let counts = self.run_git(&["rev-list", "--count", "--left-right", "@{upstream}...HEAD"], "Ahead/behind count")?;
+        let mut parsed = counts.split_whitespace().map(|count| count.parse::<usize>());
+        match (parsed.next(), parsed.next()) {
+            (Some(Ok(behind)), Some(Ok(ahead))) => Ok(UpstreamStatus { upstream: Some(upstream), ahead, behind }),
+            _ => Err(anyhow!("Unexpected rev-list output: {}", counts)),
+        }
+    }
+
+    /// Runs the independent status queries in parallel, which matters in large
+    /// repositories where `git status` alone can take seconds. Every failed query
+    /// is reported, not just the first.
+    fn status_snapshot(&self) -> Result<StatusSnapshot> {
+        fn joined<T>(handle: thread::ScopedJoinHandle<'_, Result<T>>) -> Result<T> {
+            handle.join().unwrap_or_else(|_| Err(anyhow!("query thread panicked")))
+        }
+
+        let (branch, porcelain, upstream) = thread::scope(|scope| {
+            let branch = scope.spawn(|| self.get_current_branch());
+            let porcelain = scope.spawn(|| self.run_git(&["status", "--porcelain=v1"], "Status"));
+            let upstream = scope.spawn(|| self.upstream_status());
+            (joined(branch), joined(porcelain), joined(upstream))
+        });
+
+        match (branch, porcelain, upstream) {
+            (Ok(branch), Ok(porcelain), Ok(upstream)) => Ok(StatusSnapshot {
+                branch,
+                has_changes: !porcelain.is_empty(),
+                entries: porcelain.lines().filter_map(StatusEntry::parse).collect(),
+                upstream,
+            }),
+            (branch, porcelain, upstream) => {
+                let problems: Vec<String> = [
+                    branch.err().map(|e| format!("branch: {:#}", e)),
+                    porcelain.err().map(|e| format!("working tree: {:#}", e)),
+                    upstream.err().map(|e| format!("upstream: {:#}", e)),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                Err(anyhow!("Failed to read repository status:\n  - {}", problems.join("\n  - ")))
+            }
+        }
+    }
+
+    fn with_network_retries<T, F>(&self, action: &str, operation: F) -> Result<T>
+    where
+        F: Fn() -> Result<T>,
+    {
+        retry_network_errors(action, self.config.network_retries, operation, thread::sleep)
+    }
+
+    fn pull(&self, abort_on_conflict: bool) -> Result<()> {
+        let strategy = self.config.pull_strategy.as_str();
+
+        if self.dry_run {
+            self.record(PlanStep::Pull { remote: self.remote_override.clone(), strategy: strategy.to_string() });
+            match &self.remote_override {
+                Some(remote) => info!("[DRY RUN] Would pull changes from {} using {} strategy", remote, strategy),
+                None => info!("[DRY RUN] Would pull changes using {} strategy", strategy),
+            }
+            return Ok(());
+        }
+
+        let mut args = vec!["pull".to_string()];
+        match strategy {
+            "rebase" => args.push("--rebase".to_string()),
+            "ff-only" => args.push("--ff-only".to_string()),
+            _ => args.push("--no-rebase".to_string()),
+        }
+        if self.stream {
+            args.push("--progress".to_string());
+        }
+        if let Some(remote) = &self.remote_override {
+            args.push(remote.clone());
+            args.push(self.get_current_branch()?);
+        }
+
+        self.with_network_retries("pull", || {
+            let output = self
+                .git_output(&args, self.stream)
+                .map_err(|e| self.spawn_error("Failed to pull changes", e))?;
+
+            if !output.success {
+                let conflicts = self.conflicted_files()?;
+                if !conflicts.is_empty() {
+                    let operation = if strategy == "rebase" { "rebase" } else { "merge" };
+                    return Err(self.conflict_error("Pull", operation, &conflicts, abort_on_conflict));
+                }
+
+                let err_msg = &output.stderr;
+                error!("Pull failed: {}", err_msg);
+                return Err(GitError::CommandFailed { action: String::from("Pull"), stderr: err_msg.clone() }.into());
+            }
+
+            Ok(())
+        })
+    }
+
+    fn fetch(&self, all: bool, prune: bool) -> Result<Vec<String>> {
+        let mut args = vec!["fetch"];
+        if all {
+            args.push("--all");
+        }
+        if prune {
+            args.push("--prune");
+        }
+        if self.stream {
+            args.push("--progress");
+        }
+        if !all {
+            args.push(self.remote());
+        }
+
+        if self.dry_run {
+            self.record(PlanStep::Fetch { args: args.iter().map(|arg| arg.to_string()).collect() });
+            info!("[DRY RUN] Would run: git {}", args.join(" "));
+            return Ok(Vec::new());
+        }
+
+        self.with_network_retries("fetch", || {
+            let output = self
+                .git_output(&args, self.stream)
+                .map_err(|e| self.spawn_error("Failed to fetch", e))?;
+
+            if !output.success {
+                error!("Fetch failed: {}", output.stderr);
+                return Err(GitError::CommandFailed { action: String::from("Fetch"), stderr: output.stderr }.into());
+            }
+
+            Ok(parse_fetch_updates(&output.stderr))
+        })
+    }
+
+    fn rebase(&self, onto: &str, interactive: bool) -> Result<()> {
+        if self.has_changes()? {
+            return Err(anyhow!("Working tree has uncommitted changes; commit or stash them before rebasing"));
+        }
+
+        if self.dry_run {
+            self.record(PlanStep::Rebase { onto: onto.to_string(), interactive });
+            let mode = if interactive { "interactive rebase" } else { "rebase" };
+            info!("[DRY RUN] Would {} {} onto {}", mode, self.get_current_branch()?, onto);
+            return Ok(());
+        }
+
+        let (success, err_msg) = if interactive {
+            let status = self
+                .git_command()
+                .args(["rebase", "-i", onto])
+                .status()
+                .map_err(|e| anyhow!("Failed to rebase: {}", e))?;
+            self.log_operation(&["rebase", "-i", onto], status);
+            (status.success(), String::new())
+        } else {
+            let output = self
+                .git_output(&["rebase", onto], false)
+                .map_err(|e| self.spawn_error("Failed to rebase", e))?;
+            (output.success, output.stderr)
+        };
+
+        if !success {
+            let conflicts = self.conflicted_files()?;
+            if !conflicts.is_empty() {
+                return Err(self.conflict_error("Rebase", "rebase", &conflicts, false));
+            }
+            error!("Rebase failed: {}", err_msg);
+            return Err(GitError::CommandFailed {
+                action: format!("Rebase onto {}", onto),
+                stderr: err_msg.trim_end().to_string(),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    fn merge(&self, branch: &str, no_ff: bool, ff_only: bool) -> Result<()> {
+        let exists = self.git_succeeds(&["rev-parse", "--verify", "--quiet", &format!("refs/heads/{}", branch)])
+            || self.git_succeeds(&["rev-parse", "--verify", "--quiet", &format!("refs/remotes/{}", branch)]);
+        if !exists {
+            return Err(anyhow!("Branch {} does not exist", branch));
+        }
+        if self.has_tracked_changes()? {
+            return Err(anyhow!("Working tree has uncommitted changes; commit or stash them before merging"));
+        }
+
+        let mut args = vec!["merge"];
+        if no_ff {
+            args.push("--no-ff");
+        }
+        if ff_only {
+            args.push("--ff-only");
+        }
+        args.push(branch);
+
+        if self.dry_run {
+            self.record(PlanStep::Merge { branch: branch.to_string(), no_ff, ff_only });
+            info!("[DRY RUN] Would merge {} into {} (git {})", branch, self.get_current_branch()?, args.join(" "));
+            return Ok(());
+        }
+
+        let output = self
+            .git_output(&args, false)
+            .map_err(|e| self.spawn_error("Failed to merge", e))?;
+
+        if !output.success {
+            let conflicts = self.conflicted_files()?;
+            if !conflicts.is_empty() {
+                return Err(self.conflict_error("Merge", "merge", &conflicts, false));
+            }
+            error!("Merge failed: {}", output.stderr);
+            let error = anyhow::Error::from(GitError::CommandFailed {
+                action: format!("Merge of {}", branch),
+                stderr: output.stderr.trim_end().to_string(),
+            });
+            if ff_only && output.stderr.contains("Not possible to fast-forward") {
+                return Err(error.context(format!(
+                    "Cannot fast-forward to {}; rerun without --ff-only to create a merge commit",
+                    branch
+                )));
+            }
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
+    fn cherry_pick(&self, commit: &str, no_commit: bool) -> Result<()> {
+        if !self.git_succeeds(&["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", commit)]) {
+            return Err(anyhow!("Cannot cherry-pick: {} does not resolve to a commit", commit));
+        }
+
+        if self.dry_run {
+            self.record(PlanStep::CherryPick { commit: commit.to_string(), no_commit });
+            if no_commit {
+                info!("[DRY RUN] Would apply {} without committing", commit);
+            } else {
+                info!("[DRY RUN] Would cherry-pick {}", commit);
+            }
+            return Ok(());
+        }
+
+        let mut args = vec!["cherry-pick"];
+        if no_commit {
+            args.push("--no-commit");
+        }
+        args.push(commit);
+
+        let output = self
+            .git_output(&args, false)
+            .map_err(|e| self.spawn_error("Failed to cherry-pick", e))?;
+
+        if !output.success {
+            let conflicts = self.conflicted_files()?;
+            if !conflicts.is_empty() {
+                return Err(self.conflict_error("Cherry-pick", "cherry-pick", &conflicts, false));
+            }
+            error!("Cherry-pick failed: {}", output.stderr);
+            return Err(GitError::CommandFailed {
+                action: format!("Cherry-pick of {}", commit),
+                stderr: output.stderr.trim_end().to_string(),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// The merge, rebase, cherry-pick or revert that is stopped half-way, if any.
+    fn operation_in_progress(&self) -> Option<&'static str> {
+        const MARKERS: &[(&str, &str)] = &[
+            ("MERGE_HEAD", "merge"),
+            ("rebase-merge", "rebase"),
+            ("rebase-apply", "rebase"),
+            ("CHERRY_PICK_HEAD", "cherry-pick"),
+            ("REVERT_HEAD", "revert"),
+        ];
+        MARKERS.iter().find_map(|(marker, operation)| {
+            let path = self.run_git(&["rev-parse", "--git-path", marker], "Git path lookup").ok()?;
+            Path::new(&path).exists().then_some(*operation)
+        })
+    }
+
+    fn conflicted_files(&self) -> Result<Vec<String>> {
+        Ok(self
+            .run_git(&["diff", "--name-only", "--diff-filter=U"], "Conflict check")?
+            .lines()
+            .map(String::from)
+            .collect())
+    }
+
+    fn abort_operation(&self, operation: &str) -> Result<()> {
+        self.run_git(&[operation, "--abort"], &format!("Aborting {}", operation))?;
+        Ok(())
+    }
+
+    fn conflict_error(&self, action: &str, operation: &str, conflicts: &[String], abort: bool) -> anyhow::Error {
+        error!("{} stopped with conflicts in: {}", action, conflicts.join(", "));
+
+        let hint = if abort {
+            match self.abort_operation(operation) {
+                Ok(()) => format!("The {} was aborted (git {} --abort)", operation, operation),
+                Err(e) => format!("It could not be aborted: {}", e),
+            }
+        } else {
+            format!(
+                "Resolve them and run `git {} --continue`, or run `git {} --abort` to back out",
+                operation,
+                operation
+            )
+        };
+
+        GitError::MergeConflict { action: action.to_string(), files: conflicts.to_vec(), hint }.into()
+    }
+
+    fn run_pre_commit_command(&self, command: &str) -> Result<()> {
+        if self.dry_run {
+            self.record(PlanStep::Check { command: command.to_string() });
+            info!("[DRY RUN] Would run pre-commit command: {}", command);
+            return Ok(());
+        }
+
+        info!("Running pre-commit command: {}", command);
+        let output = shell_command(command)
+            .output()
+            .map_err(|e| anyhow!("Failed to run pre-commit command: {}", e))?;
+
+        if !output.status.success() {
+            io::stdout().write_all(&output.stdout)?;
+            io::stderr().write_all(&output.stderr)?;
+            let status = output.status.code().map(|code| code.to_string()).unwrap_or_else(|| String::from("signal"));
+            error!("Pre-commit command failed with status {}", status);
+            return Err(anyhow!(
+                "Pre-commit command `{}` failed (exit status {}); fix the problem or rerun with --skip-checks",
+                command,
+                status
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn run_post_push_command(&self, command: &str, branch: &str, remote: &str) -> Result<()> {
+        info!("Running post-push command: {}", command);
+        // Inherit stdio so tools like `gh pr create` can print URLs or prompt.
+        let status = shell_command(command)
+            .env("GIT_AUTOMATE_BRANCH", branch)
+            .env("GIT_AUTOMATE_REMOTE", remote)
+            .status()
+            .map_err(|e| anyhow!("Failed to run post-push command: {}", e))?;
+
+        if !status.success() {
+            let code = status.code().map(|code| code.to_string()).unwrap_or_else(|| String::from("signal"));
+            if self.config.post_push_fatal {
+                error!("Post-push command failed with status {}", code);
+                return Err(anyhow!("Post-push command `{}` failed (exit status {})", command, code));
+            }
+            warn!("Post-push command `{}` failed (exit status {}); the push itself succeeded", command, code);
+        }
+
+        Ok(())
+    }
+
+    fn add_files(&self, files: &[String], update_only: bool) -> Result<()> {
+        if self.dry_run {
+            self.record(PlanStep::Add { files: files.to_vec(), update_only });
+            if update_only {
+                info!("[DRY RUN] Would add tracked files only (git add -u): {:?}", files);
+            } else {
+                info!("[DRY RUN] Would add files: {:?}", files);
+            }
+            return Ok(());
+        }
+
+        let mut args = vec!["add"];
+        if update_only {
+            args.push("-u");
+        }
+        args.extend(files.iter().map(String::as_str));
+        self.run_git(&args, "Add")?;
+        Ok(())
+    }
+
+    /// Paths `git add` would stage for these pathspecs, from its `--dry-run` report.
+    fn files_to_stage(&self, files: &[String], update_only: bool) -> Result<Vec<String>> {
+        let mut args = vec!["add", "--dry-run"];
+        if update_only {
+            args.push("-u");
+        }
+        args.push("--");
+        args.extend(files.iter().map(String::as_str));
+
+        Ok(self
+            .run_git(&args, "Add preview")?
+            .lines()
+            .filter_map(|line| line.strip_prefix("add '")?.strip_suffix('\''))
+            .map(String::from)
+            .collect())
+    }
+
+    /// Pathspecs that name no existing file and match nothing git knows about,
+    /// tracked or untracked. git treats globs itself, since no shell is involved.
+    fn unmatched_pathspecs(&self, files: &[String]) -> Vec<String> {
+        files
+            .iter()
+            .filter(|file| !Path::new(file.as_str()).exists())
+            .filter(|file| {
+                self.run_git(&["ls-files", "--cached", "--others", "--exclude-standard", "--", file], "Pathspec lookup")
+                    .map(|matches| matches.is_empty())
+                    .unwrap_or(true)
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn add_patch(&self, files: &[String]) -> Result<()> {
+        if self.dry_run {
+            self.record(PlanStep::AddPatch { files: files.to_vec() });
+            info!("[DRY RUN] Would interactively pick hunks to stage (git add -p): {:?}", files);
+            return Ok(());
+        }
+
+        let mut args = vec!["add", "-p", "--"];
+        args.extend(files.iter().map(String::as_str));
+        let status = self
+            .git_command()
+            .args(&args)
+            .status()
+            .map_err(|e| anyhow!("Failed to run git add -p: {}", e))?;
+        self.log_operation(&args, status);
+        if !status.success() {
+            error!("Interactive add failed with {}", status);
+            return Err(anyhow!("Interactive add (git add -p) failed ({})", status));
+        }
+        Ok(())
+    }
+
+    fn commit_option_args(&self, options: &CommitOptions) -> Vec<String> {
+        let mut args = Vec::new();
+        if options.no_verify {
+            args.push(String::from("--no-verify"));
+        }
+        if options.allow_empty {
+            args.push(String::from("--allow-empty"));
+        }
+        if let Some(author) = &options.author {
+            args.push(format!("--author={}", author.trim()));
+        }
+        if options.sign_off.is_some() {
+            args.push(String::from("--signoff"));
+        }
+        if options.sign {
+            match &self.config.signing_key {
+                Some(key) => args.push(format!("--gpg-sign={}", key)),
+                None => args.push(String::from("-S")),
+            }
+        }
+        args
+    }
+
+    fn log_commit_options(&self, options: &CommitOptions) {
+        if options.no_verify {
+            info!("[DRY RUN] Would skip commit hooks (--no-verify)");
+        }
+        if options.sign {
+            match &self.config.signing_key {
+                Some(key) => info!("[DRY RUN] Would sign the commit with key {}", key),
+                None => info!("[DRY RUN] Would sign the commit with the default key"),
+            }
+        }
+        if let Some(author) = &options.author {
+            info!("[DRY RUN] Would set the commit author to {}", author.trim());
+        }
+        if let Some(identity) = &options.sign_off {
+            info!("[DRY RUN] Would sign off as {}", identity);
+        }
+    }
+
+    fn commit_failure(&self, action: &str, options: &CommitOptions, err_msg: &str) -> anyhow::Error {
+        error!("{} failed: {}", action, err_msg);
+        let error = anyhow::Error::from(GitError::CommandFailed {
+            action: action.to_string(),
+            stderr: err_msg.trim().to_string(),
+        });
+        if options.sign && (err_msg.contains("failed to sign") || err_msg.contains("gpg")) {
+            return error.context(format!(
+                "{} failed while signing; check your signing setup (user.signingkey, gpg.format)",
+                action
+            ));
+        }
+        error
+    }
+
+    fn commit(&self, message: &str, options: &CommitOptions) -> Result<()> {
+        if self.dry_run {
+            self.record(PlanStep::Commit {
+                message: message.to_string(),
+                no_verify: options.no_verify,
+                sign: options.sign,
+                allow_empty: options.allow_empty,
+                author: options.author.clone(),
+            });
+            self.log_commit_options(options);
+            let message = match &options.sign_off {
+                Some(identity) => append_trailers(message, &[format!("Signed-off-by: {}", identity)]),
+                None => message.to_string(),
+            };
+            info!("[DRY RUN] Would commit with message: {}", message);
+            return Ok(());
+        }
+
+        let mut args = vec![String::from("commit"), String::from("-m"), message.to_string()];
+        args.extend(self.commit_option_args(options));
+
+        let output = self
+            .git_output(&args, false)
+            .map_err(|e| self.spawn_error("Failed to commit", e))?;
+
+        if !output.success {
+            return Err(self.commit_failure("Commit", options, &output.stderr));
+        }
+
+        Ok(())
+    }
+
+    /// Runs a plain `git commit` on the terminal so the user's editor opens;
+    /// trailers are passed with `--trailer` since there is no message to append to.
+    fn commit_in_editor(&self, trailers: &[String], options: &CommitOptions) -> Result<()> {
+        if self.dry_run {
+            self.record(PlanStep::CommitInEditor {
+                trailers: trailers.to_vec(),
+                no_verify: options.no_verify,
+                sign: options.sign,
+                allow_empty: options.allow_empty,
+                author: options.author.clone(),
+            });
+            self.log_commit_options(options);
+            info!("[DRY RUN] Would open the editor to write the commit message");
+            return Ok(());
+        }
+
+        let mut args = vec![String::from("commit")];
+        for trailer in trailers {
+            args.extend([String::from("--trailer"), trailer.clone()]);
+        }
+        args.extend(self.commit_option_args(options));
+
+        let status = self
+            .git_command()
+            .args(&args)
+            .status()
+            .map_err(|e| anyhow!("Failed to commit: {}", e))?;
+        self.log_operation(&args, status);
+        if !status.success() {
+            error!("Commit failed with {}", status);
+            return Err(anyhow!("Commit was aborted or failed ({}); nothing was pushed", status));
+        }
+
+        Ok(())
+    }
+
+    fn commit_amend(&self, message: Option<&str>, options: &CommitOptions) -> Result<()> {
+        if self.dry_run {
+            self.record(PlanStep::Amend {
+                message: message.map(String::from),
+                no_verify: options.no_verify,
+                sign: options.sign,
+                author: options.author.clone(),
+            });
+            self.log_commit_options(options);
+            match message {
+                Some(message) => info!("[DRY RUN] Would amend last commit with message: {}", message),
+                None => info!("[DRY RUN] Would amend last commit keeping its message"),
+            }
+            return Ok(());
+        }
+
+        let mut args = amend_args(message);
+        args.extend(self.commit_option_args(options));
+
+        let output = self
+            .git_output(&args, false)
+            .map_err(|e| self.spawn_error("Failed to amend commit", e))?;
+
+        if !output.success {
+            return Err(self.commit_failure("Amend", options, &output.stderr));
+        }
+
+        Ok(())
+    }
+
+    fn has_upstream(&self, branch: &str) -> bool {
+        self.git_succeeds(&["rev-parse", "--abbrev-ref", &format!("{}@{{upstream}}", branch)])
+    }
+
+    /// Asks once per run before pushing to a remote other than default_remote,
+    /// since an explicit --remote is usually a one-off mirror.
+    fn confirm_non_default_remote(&self) -> Result<bool> {
+        let remote = self.remote();
+        let default = &self.config.default_remote;
+        if remote == default {
+            return Ok(true);
+        }
+        if self.dry_run {
+            info!("[DRY RUN] {} is not the default remote ({}); a real run would ask before pushing", remote, default);
+            return Ok(true);
+        }
+        if let Some(confirmed) = self.remote_confirmed.get() {
+            return Ok(*confirmed);
+        }
+
+        let confirmed = self.assume_yes
+            || !io::stdin().is_terminal()
+            || confirm(&format!("Push to {} instead of {}?", remote, default))?;
+        Ok(*self.remote_confirmed.get_or_init(|| confirmed))
+    }
+
+    fn push(&self, branch: &str, force: ForceMode, no_verify: bool) -> Result<()> {
+        self.push_with_upstream(branch, None, force, no_verify)
+    }
+
+    /// Pushes `branch`, optionally to an explicit `(remote, branch)` upstream that
+    /// is then tracked; a differently named upstream is confirmed first.
+    fn push_with_upstream(
+        &self,
+        branch: &str,
+        upstream: Option<(&str, &str)>,
+        force: ForceMode,
+        no_verify: bool,
+    ) -> Result<()> {
+        let set_upstream = upstream.is_some() || !self.has_upstream(branch);
+        let (remote, upstream_branch) = upstream.unwrap_or((self.remote(), branch));
+
+        if upstream.is_none() && !self.confirm_non_default_remote()? {
+            return Err(anyhow!("Push to {} cancelled; nothing was pushed", remote));
+        }
+        if upstream_branch != branch {
+            if self.dry_run {
+                info!("[DRY RUN] Would push {} as {}/{}; a real run would ask first", branch, remote, upstream_branch);
+            } else if !self.assume_yes
+                && io::stdin().is_terminal()
+                && !confirm(&format!("Push {} to {}/{} and track it?", branch, remote, upstream_branch))?
+            {
+                return Err(anyhow!("Push to {}/{} cancelled; nothing was pushed", remote, upstream_branch));
+            }
+        }
+
+        if self.dry_run {
+            self.record(PlanStep::Push {
+                remote: remote.to_string(),
+                branch: branch.to_string(),
+                upstream_branch: (upstream_branch != branch).then(|| upstream_branch.to_string()),
+                force,
+                set_upstream,
+                no_verify,
+                follow_tags: self.config.push_tags,
+            });
+            if no_verify {
+                info!("[DRY RUN] Would skip the pre-push hook (--no-verify)");
+            }
+            if self.config.push_tags {
+                info!("[DRY RUN] Would include annotated tags reachable from {} (--follow-tags)", branch);
+            }
+            match force {
+                ForceMode::None => {}
+                ForceMode::WithLease => info!("[DRY RUN] Would force push (with lease) to {}", branch),
+                ForceMode::Unsafe => info!("[DRY RUN] Would force push (without lease) to {}", branch),
+            }
+            if set_upstream {
+                info!("[DRY RUN] Would push to {} with --set-upstream {}", upstream_branch, remote);
+            } else {
+                info!("[DRY RUN] Would push to {} on {}", branch, remote);
+            }
+            return Ok(());
+        }
+
+        let refspec = if upstream_branch == branch {
+            branch.to_string()
+        } else {
+            format!("{}:{}", branch, upstream_branch)
+        };
+        let mut args = vec!["push"];
+        if upstream.is_some() {
+            info!("Setting upstream of {} to {}/{}", branch, remote, upstream_branch);
+            args.push("--set-upstream");
+        } else if set_upstream {
+            info!("No upstream configured for {}, setting upstream to {}", branch, remote);
+            args.push("--set-upstream");
+        }
+        match force {
+            ForceMode::None => {}
+            ForceMode::WithLease => args.push("--force-with-lease"),
+            ForceMode::Unsafe => {
+                warn!("Force pushing without lease to {}", branch);
+                args.push("--force");
+            }
+        }
+        if no_verify {
+            args.push("--no-verify");
+        }
+        if self.config.push_tags {
+            // --follow-tags combines with --set-upstream and forcing; --tags would replace the refspec.
+            args.push("--follow-tags");
+        }
+        if self.stream {
+            args.push("--progress");
+        }
+        args.extend([remote, refspec.as_str()]);
+
+        self.with_network_retries("push", || {
+            let output = self
+                .git_output(&args, self.stream)
+                .map_err(|e| self.spawn_error("Failed to push", e))?;
+
+            if !output.success {
+                let err_msg = &output.stderr;
+                error!("Push failed: {}", err_msg);
+                if err_msg.contains("[rejected]") || err_msg.contains("[remote rejected]") {
+                    return Err(GitError::PushRejected(format!("Push rejected: {}", err_msg)).into());
+                }
+                return Err(GitError::CommandFailed { action: String::from("Push"), stderr: err_msg.clone() }.into());
+            }
+
+            Ok(())
+        })?;
+
+        if let Some(command) = &self.config.post_push_command {
+            self.run_post_push_command(command, branch, remote)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the full name of the new branch, including any configured prefix.
+    fn create_branch(
+        &self,
+        name: &str,
+        branch_type: Option<&str>,
+        use_prefix: bool,
+        from: Option<&str>,
+    ) -> Result<String> {
+        let prefix = match &self.config.branch_prefix {
+            Some(prefix) if use_prefix && !name.contains('/') => Some(self.expand_branch_prefix(prefix)?),
+            _ => None,
+        };
+        let name = compose_branch_name(prefix.as_deref(), branch_type, name);
+        validate_branch_name(&name)?;
+        if let Some(base) = from {
+            self.ensure_commit(base)
+                .map_err(|_| anyhow!("Cannot branch from {}: no such branch or commit", base))?;
+        }
+        let track = from.is_some_and(|base| {
+            self.git_succeeds(&["rev-parse", "--verify", "--quiet", &format!("refs/remotes/{}", base)])
+        });
+
+        if self.dry_run {
+            self.record(PlanStep::CreateBranch { name: name.clone(), from: from.map(String::from), track });
+            match from {
+                Some(base) if track => info!("[DRY RUN] Would create branch {} from {}, tracking it", name, base),
+                Some(base) => info!("[DRY RUN] Would create branch {} from {}", name, base),
+                None => info!("[DRY RUN] Would create branch: {}", name),
+            }
+            return Ok(name);
+        }
+
+        let mut args = vec!["checkout", "-b", &name];
+        if track {
+            args.push("--track");
+        }
+        args.extend(from);
+        self.run_git(&args, "Branch creation")?;
+        info!("Created branch {}", name);
+        Ok(name)
+    }
+
+    fn expand_branch_prefix(&self, prefix: &str) -> Result<String> {
+        if !prefix.contains("{user}") {
+            return Ok(prefix.to_string());
+        }
+
+        let user = self
+            .run_git(&["config", "user.name"], "User lookup")
+            .ok()
+            .or_else(|| std::env::var("USER").ok())
+            .map(|user| user.split_whitespace().collect::<Vec<_>>().join("-").to_lowercase())
+            .filter(|user| !user.is_empty())
+            .ok_or_else(|| anyhow!("branch_prefix uses {{user}} but neither git user.name nor $USER is set"))?;
+        Ok(prefix.replace("{user}", &user))
+    }
+
+    fn switch_branch(&self, name: &str, stash: bool) -> Result<()> {
+        validate_branch_name(name)?;
+
+        // Untracked files are carried across a checkout, so only tracked changes count as dirty.
+        let dirty = self.has_tracked_changes()?;
+        if dirty && !stash {
+            return Err(anyhow!(
+                "Working tree has uncommitted changes; commit or stash them before switching, or rerun with --stash"
+            ));
+        }
+        let stash_message = format!("git-automate: switching to {}", name);
+
+        if self.dry_run {
+            if dirty {
+                self.stash_save(Some(&stash_message))?;
+            }
+            self.record(PlanStep::SwitchBranch { name: name.to_string() });
+            info!("[DRY RUN] Would switch to branch: {}", name);
+            if dirty {
+                self.record(PlanStep::StashPop);
+                info!("[DRY RUN] Would pop the stashed changes on {}", name);
+            }
+            return Ok(());
+        }
+
+        if dirty {
+            self.stash_save(Some(&stash_message))?;
+        }
+
+        if let Err(e) = self.run_git(&["checkout", name], "Branch switch") {
+            if dirty {
+                if let Err(pop_error) = self.run_git(&["stash", "pop"], "Stash pop") {
+                    return Err(e.context(format!("Restoring stashed changes also failed: {}", pop_error)));
+                }
+            }
+            return Err(e);
+        }
+
+        if dirty {
+            let output = self
+                .git_output(&["stash", "pop"], false)
+                .map_err(|e| self.spawn_error("Failed to pop stash", e))?;
+            if !output.success {
+                let conflicts = self.conflicted_files()?;
+                if !conflicts.is_empty() {
+                    error!("Stash pop stopped with conflicts in: {}", conflicts.join(", "));
+                    return Err(GitError::MergeConflict {
+                        action: format!("Switched to {} but re-applying the stashed changes", name),
+                        files: conflicts,
+                        hint: String::from("Resolve them, then run `git stash drop` to discard the kept stash entry"),
+                    }
+                    .into());
+                }
+                error!("Stash pop failed: {}", output.stderr);
+                return Err(anyhow!(
+                    "Switched to {} but re-applying the stashed changes failed; they are kept in the stash: {}",
+                    name,
+                    output.stderr.trim_end()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rename_branch(&self, old: Option<&str>, new: &str, push: bool) -> Result<()> {
+        validate_branch_name(new)?;
+        let old = match old {
+            Some(old) => old.to_string(),
+            None => self.get_current_branch()?,
+        };
+        let had_upstream = self.has_upstream(&old);
+
+        if self.dry_run {
+            self.record(PlanStep::RenameBranch { old: old.clone(), new: new.to_string() });
+            info!("[DRY RUN] Would rename branch {} to {}", old, new);
+        } else {
+            self.run_git(&["branch", "-m", &old, new], "Branch rename")?;
+        }
+
+        if !had_upstream {
+            return Ok(());
+        }
+        if !push {
+            warn!("Branch {} had an upstream; rerun with --push to push {} and track it", old, new);
+            return Ok(());
+        }
+
+        if self.dry_run {
+            self.record(PlanStep::Push {
+                remote: self.remote().to_string(),
+                branch: new.to_string(),
+                upstream_branch: None,
+                force: ForceMode::None,
+                set_upstream: true,
+                no_verify: false,
+                follow_tags: false,
+            });
+            info!("[DRY RUN] Would push {} to {} with --set-upstream", new, self.remote());
+            return Ok(());
+        }
+
+        self.with_network_retries("push", || {
+            self.run_git(&["push", "--set-upstream", self.remote(), new], "Push")?;
+            Ok(())
+        })
+    }
+
+    fn delete_branch(&self, name: &str, force: bool) -> Result<()> {
+        let flag = if force { "-D" } else { "-d" };
+
+        if self.dry_run {
+            self.record(PlanStep::DeleteBranch { name: name.to_string(), force });
+            if force {
+                info!("[DRY RUN] Would force delete branch (git branch -D): {}", name);
+            } else {
+                info!("[DRY RUN] Would delete branch (git branch -d): {}", name);
+            }
+            return Ok(());
+        }
+
+        let output = self
+            .git_output(&["branch", flag, name], false)
+            .map_err(|e| self.spawn_error("Failed to delete branch", e))?;
+
+        if !output.success {
+            let err_msg = &output.stderr;
+            error!("Branch deletion failed: {}", err_msg);
+            if !force && err_msg.contains("not fully merged") {
+                return Err(anyhow!(
+                    "Branch {} is not fully merged; rerun with --force to delete it anyway",
+                    name
+                ));
+            }
+            return Err(GitError::CommandFailed {
+                action: String::from("Branch deletion"),
+                stderr: err_msg.clone(),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    fn merged_branches(&self, target: &str) -> Result<Vec<String>> {
+        Ok(self
+            .run_git(&["branch", "--format=%(refname:short)", "--merged", target], "Merged branch lookup")?
+            .lines()
+            .map(String::from)
+            .collect())
+    }
+
+    /// Returns the pruned refs; with `preview` (or under dry-run) only reports them.
+    fn remote_prune(&self, preview: bool) -> Result<Vec<String>> {
+        if self.dry_run {
+            self.record(PlanStep::RemotePrune { remote: self.remote().to_string() });
+        }
+        let mut args = vec!["remote", "prune"];
+        if preview || self.dry_run {
+            args.push("--dry-run");
+        }
+        args.push(self.remote());
+
+        let output = self.run_git(&args, "Remote prune")?;
+        Ok(output
+            .lines()
+            .map(str::trim)
+            .filter_map(|line| line.strip_prefix("* [would prune] ").or(line.strip_prefix("* [pruned] ")))
+            .map(String::from)
+            .collect())
+    }
+
+    fn list_branches(&self, all: bool) -> Result<Vec<String>> {
+        let mut args = vec!["branch", "--format=%(refname:short)"];
+        if all {
+            args.push("--all");
+        }
+
+        Ok(self.run_git(&args, "Branch list")?
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect())
+    }
+
+    /// Lists branches with their tracking state in one `for-each-ref` call.
+    fn branch_infos(&self, all: bool) -> Result<Vec<BranchInfo>> {
+        let mut args = vec![
+            "for-each-ref",
+            "--format=%(HEAD)%00%(refname)%00%(refname:short)%00%(upstream:short)%00%(upstream:track,nobracket)",
+            "refs/heads",
+        ];
+        if all {
+            args.push("refs/remotes");
+        }
+
+        let output = self.run_git(&args, "Branch list")?;
+        let mut branches = Vec::new();
+        for line in output.lines() {
+            let fields: Vec<&str> = line.split('\0').collect();
+            let [head, refname, name, upstream, track] = fields[..] else {
+                continue;
+            };
+            // Skip symbolic refs such as origin/HEAD.
+            if refname.ends_with("/HEAD") {
+                continue;
+            }
+
+            let mut tracking = UpstreamStatus {
+                upstream: Some(upstream.to_string()).filter(|upstream| !upstream.is_empty()),
+                ..UpstreamStatus::default()
+            };
+            for part in track.split(", ") {
+                match part.split_once(' ') {
+                    Some(("ahead", count)) => tracking.ahead = count.parse().unwrap_or(0),
+                    Some(("behind", count)) => tracking.behind = count.parse().unwrap_or(0),
+                    _ => {}
+                }
+            }
+
+            branches.push(BranchInfo {
+                name: name.to_string(),
+                current: head == "*",
+                remote: refname.starts_with("refs/remotes/"),
+                gone: track == "gone",
+                tracking,
+            });
+        }
+
+        Ok(branches)
+    }
+
+    fn tag_exists(&self, name: &str) -> bool {
+        self.git_succeeds(&["rev-parse", "--verify", "--quiet", &format!("refs/tags/{}", name)])
+    }
+
+    /// Resolves the identity `git commit --signoff` will use when sign-off is
+    /// requested here or by config, failing early if git has none configured.
+    fn sign_off_identity(&self, requested: bool) -> Result<Option<String>> {
+        if !requested && !self.config.sign_off {
+            return Ok(None);
+        }
+        let lookup = |key: &str| self.run_git(&["config", key], "Config read").ok().filter(|value| !value.is_empty());
+        match (lookup("user.name"), lookup("user.email")) {
+            (Some(name), Some(email)) => Ok(Some(format!("{} <{}>", name, email))),
+            _ => Err(anyhow!(
+                "Cannot sign off: git user.name and user.email must both be set \
+                 (e.g. `git-automate git-config set user.email you@example.com`)"
+            )),
+        }
+    }
+
+    fn sign_tags(&self) -> bool {
+        self.config.sign_tags.unwrap_or(self.config.sign_commits)
+    }
+
+    fn create_tag(&self, name: &str, message: Option<&str>, sign: bool) -> Result<()> {
+        if self.tag_exists(name) {
+            return Err(anyhow!("Tag {} already exists", name));
+        }
+        if sign && message.is_none() {
+            return Err(anyhow!("Signed tags must be annotated; pass a message with -m or use --no-sign"));
+        }
+
+        if self.dry_run {
+            self.record(PlanStep::CreateTag { name: name.to_string(), message: message.map(String::from), sign });
+            match message {
+                Some(message) => info!("[DRY RUN] Would create annotated tag {} with message: {}", name, message),
+                None => info!("[DRY RUN] Would create lightweight tag {}", name),
+            }
+            if sign {
+                match &self.config.signing_key {
+                    Some(key) => info!("[DRY RUN] Would sign the tag with key {}", key),
+                    None => info!("[DRY RUN] Would sign the tag with the default key"),
+                }
+            }
+            return Ok(());
+        }
+
+        let mut args = vec![String::from("tag")];
+        if let Some(message) = message {
+            match (&self.config.signing_key, sign) {
+                (Some(key), true) => args.extend([String::from("-u"), key.clone()]),
+                (None, true) => args.push(String::from("-s")),
+                (_, false) => args.push(String::from("-a")),
+            }
+            // Keep Markdown headings, which the default cleanup strips as comments.
+            args.extend([String::from("--cleanup=whitespace"), String::from("-m"), message.to_string()]);
+        }
+        args.push(name.to_string());
+
+        let output = self
+            .git_output(&args, false)
+            .map_err(|e| self.spawn_error("Failed to create tag", e))?;
+        if !output.success {
+            error!("Tag creation failed: {}", output.stderr);
+            if sign {
+                return Err(anyhow!(
+                    "Tag creation failed while signing; check your signing setup (user.signingkey, gpg.format):\n{}",
+                    output.stderr.trim()
+                ));
+            }
+            return Err(GitError::CommandFailed { action: String::from("Tag creation"), stderr: output.stderr }.into());
+        }
+        Ok(())
+    }
+
+    fn push_tag(&self, name: &str) -> Result<()> {
+        if self.dry_run {
+            self.record(PlanStep::PushTag { remote: self.remote().to_string(), name: name.to_string() });
+            info!("[DRY RUN] Would push tag {} to {}", name, self.remote());
+            return Ok(());
+        }
+
+        self.run_git(&["push", self.remote(), &format!("refs/tags/{}", name)], "Tag push")?;
+        Ok(())
+    }
+
+    fn delete_tag(&self, name: &str) -> Result<()> {
+        if self.dry_run {
+            self.record(PlanStep::DeleteTag { name: name.to_string() });
+            info!("[DRY RUN] Would delete tag: {}", name);
+            return Ok(());
+        }
+
+        self.run_git(&["tag", "-d", name], "Tag deletion")?;
+        Ok(())
+    }
+
+    fn list_tags(&self) -> Result<Vec<String>> {
+        Ok(self.run_git(&["tag", "--list"], "Tag list")?
+            .lines()
+            .map(String::from)
+            .collect())
+    }
+
+    fn worktrees(&self) -> Result<Vec<Worktree>> {
+        Ok(Worktree::parse_porcelain(&self.run_git(&["worktree", "list", "--porcelain"], "Worktree list")?))
+    }
+
+    fn worktree_add(&self, path: &Path, branch: &str) -> Result<()> {
+        validate_branch_name(branch)?;
+        if path.exists() {
+            return Err(anyhow!("{} already exists; pick a new path for the worktree", path.display()));
+        }
+        if let Some(worktree) = self.worktrees()?.into_iter().find(|worktree| worktree.branch.as_deref() == Some(branch)) {
+            return Err(anyhow!("Branch {} is already checked out in {}", branch, worktree.path));
+        }
+        let create_branch = !self.git_succeeds(&["rev-parse", "--verify", "--quiet", &format!("refs/heads/{}", branch)]);
+        let path_str = path.to_string_lossy();
+
+        if self.dry_run {
+            self.record(PlanStep::WorktreeAdd {
+                path: path_str.to_string(),
+                branch: branch.to_string(),
+                create_branch,
+            });
+            if create_branch {
+                info!("[DRY RUN] Would create branch {} in a new worktree at {}", branch, path.display());
+            } else {
+                info!("[DRY RUN] Would check out {} in a new worktree at {}", branch, path.display());
+            }
+            return Ok(());
+        }
+
+        if create_branch {
+            self.run_git(&["worktree", "add", "-b", branch, &path_str], "Worktree add")?;
+        } else {
+            self.run_git(&["worktree", "add", &path_str, branch], "Worktree add")?;
+        }
+        info!("Added worktree {} on branch {}", path.display(), branch);
+        Ok(())
+    }
+
+    fn worktree_remove(&self, path: &Path, force: bool) -> Result<()> {
+        let path_str = path.to_string_lossy();
+        if !force {
+            let status = self
+                .git_output(&["-C", &path_str, "status", "--porcelain"], false)
+                .map_err(|e| self.spawn_error("Failed to check worktree status", e))?;
+            if status.success && !status.stdout.trim().is_empty() {
+                error!("Worktree {} has uncommitted changes", path.display());
+                return Err(anyhow!(
+                    "Worktree {} has uncommitted changes; commit or stash them, or pass --force to discard them",
+                    path.display()
+                ));
+            }
+        }
+
+        if self.dry_run {
+            self.record(PlanStep::WorktreeRemove { path: path_str.to_string(), force });
+            info!("[DRY RUN] Would remove worktree {}", path.display());
+            return Ok(());
+        }
+
+        let mut args = vec!["worktree", "remove"];
+        if force {
+            args.push("--force");
+        }
+        args.push(&path_str);
+        self.run_git(&args, "Worktree removal")?;
+        info!("Removed worktree {}", path.display());
+        Ok(())
+    }
+
+    fn list_remotes(&self) -> Result<Vec<String>> {
+        Ok(self.run_git(&["remote"], "Remote list")?
+            .lines()
+            .map(String::from)
+            .collect())
+    }
+
+    fn remote_urls(&self) -> Result<String> {
+        self.run_git(&["remote", "-v"], "Remote list")
+    }
+
+    fn ensure_remote_exists(&self, remote: &str) -> Result<()> {
+        let remotes = self.list_remotes()?;
+        if remotes.iter().any(|r| r == remote) {
+            return Ok(());
+        }
+
+        let available = if remotes.is_empty() { String::from("none") } else { remotes.join(", ") };
+        Err(anyhow!("Remote {} does not exist (available remotes: {})", remote, available))
+    }
+
+    fn undo_last_commit(&self, hard: bool) -> Result<()> {
+        if !self.git_succeeds(&["rev-parse", "--verify", "--quiet", "HEAD~1"]) {
+            return Err(anyhow!("Cannot undo: the current commit has no parent (HEAD~1 does not exist)"));
+        }
+
+        self.reset("HEAD~1", if hard { ResetMode::Hard } else { ResetMode::Soft })
+    }
+
+    fn reset(&self, target: &str, mode: ResetMode) -> Result<()> {
+        let commit = format!("{}^{{commit}}", target);
+        let output = self
+            .git_output(&["rev-parse", "--verify", &commit], false)
+            .map_err(|e| self.spawn_error(&format!("Failed to resolve {}", target), e))?;
+        if !output.success {
+            return Err(anyhow!(
+                "Invalid reset target {}: it does not resolve to a commit ({})",
+                target,
+                output.stderr.trim()
+            ));
+        }
+
+        if self.dry_run {
+            self.record(PlanStep::Reset { mode, target: target.to_string() });
+            info!("[DRY RUN] Would run: git reset {} {}", mode.flag(), target);
+            return Ok(());
+        }
+
+        self.run_git(&["reset", mode.flag(), target], "Reset")?;
+        Ok(())
+    }
+
+    fn clone_repo(&self, url: &str, dest: &Path) -> Result<()> {
+        if dest.exists() {
+            return Err(anyhow!("Destination {} already exists", dest.display()));
+        }
+
+        if self.dry_run {
+            self.record(PlanStep::Clone { url: url.to_string(), dest: dest.display().to_string() });
+            info!("[DRY RUN] Would run: git clone {} {}", url, dest.display());
+            return Ok(());
+        }
+
+        self.run_git(&[OsStr::new("clone"), OsStr::new(url), dest.as_os_str()], "Clone")?;
+        Ok(())
+    }
+
+    fn stash_save(&self, message: Option<&str>) -> Result<()> {
+        if self.dry_run {
+            self.record(PlanStep::StashSave { message: message.map(String::from) });
+            info!("[DRY RUN] Would stash changes{}", message.map(|m| format!(" with message: {}", m)).unwrap_or_default());
+            return Ok(());
+        }
+
+        let mut args = vec!["stash", "push"];
+        if let Some(message) = message {
+            args.extend(["-m", message]);
+        }
+
+        self.run_git(&args, "Stash")?;
+        Ok(())
+    }
+
+    fn stash_list(&self) -> Result<Vec<String>> {
+        Ok(self.run_git(&["stash", "list"], "Stash list")?
+            .lines()
+            .map(String::from)
+            .collect())
+    }
+
+    fn stash_pop(&self) -> Result<()> {
+        if self.stash_list()?.is_empty() {
+            return Err(anyhow!("No stash entries to pop"));
+        }
+
+        if self.dry_run {
+            self.record(PlanStep::StashPop);
+            info!("[DRY RUN] Would pop the most recent stash");
+            return Ok(());
+        }
+
+        self.run_git(&["stash", "pop"], "Stash pop")?;
+        Ok(())
+    }
+
+    fn stash_drop(&self, index: Option<usize>) -> Result<()> {
+        let stash_ref = index.map(|i| format!("stash@{{{}}}", i));
+
+        if self.dry_run {
+            self.record(PlanStep::StashDrop { stash: stash_ref.clone() });
+            info!("[DRY RUN] Would drop stash: {}", stash_ref.as_deref().unwrap_or("latest"));
+            return Ok(());
+        }
+
+        let mut args = vec!["stash", "drop"];
+        if let Some(stash_ref) = &stash_ref {
+            args.push(stash_ref);
+        }
+
+        self.run_git(&args, "Stash drop")?;
+        Ok(())
+    }
+
+    fn diff(&self, staged: bool, stat: bool) -> Result<String> {
+        let mut args = vec!["diff"];
+        if staged {
+            args.push("--cached");
+        }
+        if stat {
+            args.push("--stat");
+        }
+        if color_enabled() {
+            args.push("--color=always");
+        }
+
+        self.run_git(&args, "Diff")
+    }
+
+    fn log(&self, count: usize, oneline: bool, since: Option<&str>, until: Option<&str>) -> Result<String> {
+        let mut args = vec![String::from("log"), String::from("-n"), count.to_string()];
+        if oneline {
+            args.push(String::from("--oneline"));
+        }
+        if let Some(since) = since {
+            args.push(format!("--since={}", since));
+        }
+        if let Some(until) = until {
+            args.push(format!("--until={}", until));
+        }
+        if color_enabled() {
+            args.push(String::from("--color=always"));
+        }
+
+        self.run_git(&args, "Log")
+    }
+
+    fn last_commit_message(&self) -> Result<(String, String)> {
+        let subject = self.run_git(&["log", "-1", "--format=%s"], "Reading last commit")?;
+        let body = self.run_git(&["log", "-1", "--format=%b"], "Reading last commit")?;
+        Ok((subject, body))
+    }
+
+    fn blame(&self, file: &Path, range: Option<&str>, porcelain: bool) -> Result<String> {
+        if !file.exists() {
+            return Err(anyhow!("Cannot blame {}: no such file", file.display()));
+        }
+        let path = file.to_string_lossy();
+        if !self.git_succeeds(&["ls-files", "--error-unmatch", "--", &path]) {
+            return Err(anyhow!("Cannot blame {}: the file is not tracked by git", file.display()));
+        }
+
+        let mut args = vec![String::from("blame")];
+        if porcelain {
+            args.push(String::from("--line-porcelain"));
+        }
+        if let Some(range) = range {
+            validate_blame_range(range)?;
+            args.push(format!("-L{}", range));
+        }
+        args.push(String::from("--"));
+        args.push(path.into_owned());
+
+        self.run_git(&args, "Blame")
+    }
+
+    /// The branch the remote's HEAD points at, falling back to a local main or
+    /// master when the remote HEAD is not known.
+    fn default_branch(&self) -> Result<String> {
+        let remote_head = format!("refs/remotes/{}/HEAD", self.remote());
+        if let Ok(target) = self.run_git(&["symbolic-ref", "--short", "-q", &remote_head], "Default branch lookup") {
+            if let Some(branch) = target.strip_prefix(&format!("{}/", self.remote())) {
+                return Ok(branch.to_string());
+            }
+        }
+
+        ["main", "master"]
+            .into_iter()
+            .find(|branch| self.git_succeeds(&["rev-parse", "--verify", "--quiet", &format!("refs/heads/{}", branch)]))
+            .map(String::from)
+            .ok_or_else(|| {
+                anyhow!("Could not determine the default branch; set it with `git remote set-head {} -a`", self.remote())
+            })
+    }
+
+    fn last_tag(&self) -> Option<String> {
+        self.run_git(&["describe", "--tags", "--abbrev=0"], "Tag lookup").ok()
+    }
+
+    /// (short sha, subject) pairs for commits after `since`, newest first.
+    fn commits_since(&self, since: Option<&str>) -> Result<Vec<(String, String)>> {
+        if !self.git_succeeds(&["rev-parse", "--verify", "--quiet", "HEAD"]) {
+            return Ok(Vec::new());
+        }
+
+        let range = match since {
+            Some(since) => format!("{}..HEAD", since),
+            None => String::from("HEAD"),
+        };
+        let output = self.run_git(&["log", "--format=%h%x09%s", &range], "Log")?;
+        Ok(output
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(sha, subject)| (sha.to_string(), subject.to_string()))
+            .collect())
+    }
+
+    /// Runs `git clean` in preview (`-n`) or delete (`-f`) mode and returns the
+    /// affected paths. `-x` is only passed when ignored files were asked for.
+    fn clean(&self, directories: bool, ignored: bool, force: bool) -> Result<Vec<String>> {
+        let mut args = vec!["clean", if force { "-f" } else { "-n" }];
+        if directories {
+            args.push("-d");
+        }
+        if ignored {
+            args.push("-x");
+        }
+
+        let output = self.run_git(&args, "Clean")?;
+        let marker = if force { "Removing " } else { "Would remove " };
+        Ok(output.lines().filter_map(|line| line.strip_prefix(marker)).map(String::from).collect())
+    }
+
+    fn is_tracked(&self, path: &str) -> bool {
+        self.run_git(&["ls-files", "--cached", "--", path], "Pathspec lookup")
+            .is_ok_and(|matches| !matches.is_empty())
+    }
+
+    fn has_changes_to_restore(&self, path: &str, staged: bool) -> bool {
+        let args: &[&str] = if staged {
+            &["diff", "--cached", "--quiet", "--", path]
+        } else {
+            &["diff", "--quiet", "--", path]
+        };
+        !self.git_succeeds(args)
+    }
+
+    fn restore(&self, files: &[String], staged: bool) -> Result<()> {
+        if self.dry_run {
+            self.record(PlanStep::Restore { files: files.to_vec(), staged });
+            if staged {
+                info!("[DRY RUN] Would unstage: {:?}", files);
+            } else {
+                info!("[DRY RUN] Would discard working-tree changes to: {:?}", files);
+            }
+            return Ok(());
+        }
+
+        let mut args = vec!["restore"];
+        if staged {
+            args.push("--staged");
+        }
+        args.push("--");
+        args.extend(files.iter().map(String::as_str));
+        self.run_git(&args, "Restore")?;
+        info!("Restored {} path(s)", files.len());
+        Ok(())
+    }
+
+    fn is_bisecting(&self) -> bool {
+        self.run_git(&["rev-parse", "--git-path", "BISECT_LOG"], "Git path lookup")
+            .is_ok_and(|path| Path::new(&path).exists())
+    }
+
+    fn ensure_bisecting(&self) -> Result<()> {
+        if !self.is_bisecting() {
+            return Err(anyhow!("Not bisecting; start with `git-automate bisect start <good> <bad>`"));
+        }
+        Ok(())
+    }
+
+    fn ensure_commit(&self, revision: &str) -> Result<()> {
+        if !self.git_succeeds(&["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", revision)]) {
+            return Err(anyhow!("{} does not resolve to a commit", revision));
+        }
+        Ok(())
+    }
+
+    /// Returns git's progress report ("Bisecting: N revisions left ...") or the
+    /// "is the first bad commit" summary when the range is already narrowed down.
+    fn bisect_start(&self, good: &str, bad: &str) -> Result<String> {
+        if self.is_bisecting() {
+            return Err(anyhow!("A bisect is already in progress; finish it with `git-automate bisect reset`"));
+        }
+        self.ensure_commit(good)?;
+        self.ensure_commit(bad)?;
+
+        if self.dry_run {
+            self.record(PlanStep::BisectStart { good: good.to_string(), bad: bad.to_string() });
+            info!("[DRY RUN] Would start bisecting with good {} and bad {}", good, bad);
+            return Ok(String::new());
+        }
+
+        self.run_git(&["bisect", "start", bad, good], "Bisect start")
+    }
+
+    fn bisect_mark(&self, term: &str, commit: Option<&str>) -> Result<String> {
+        self.ensure_bisecting()?;
+        if let Some(commit) = commit {
+            self.ensure_commit(commit)?;
+        }
+
+        if self.dry_run {
+            self.record(PlanStep::BisectMark { term: term.to_string(), commit: commit.map(String::from) });
+            info!("[DRY RUN] Would mark {} as {}", commit.unwrap_or("HEAD"), term);
+            return Ok(String::new());
+        }
+
+        let mut args = vec!["bisect", term];
+        args.extend(commit);
+        self.run_git(&args, "Bisect")
+    }
+
+    /// Streams the test command's output and returns the first bad commit.
+    fn bisect_run(&self, command: &str) -> Result<Option<String>> {
+        self.ensure_bisecting()?;
+
+        if self.dry_run {
+            self.record(PlanStep::BisectRun { command: command.to_string() });
+            info!("[DRY RUN] Would run `{}` on each bisect step", command);
+            return Ok(None);
+        }
+
+        let shell = if cfg!(windows) { ["cmd", "/C"] } else { ["sh", "-c"] };
+        let args = ["bisect", "run", shell[0], shell[1], command];
+        let status = self
+            .git_command()
+            .args(args)
+            .status()
+            .map_err(|e| anyhow!("Failed to run git bisect: {}", e))?;
+        self.log_operation(&args, status);
+        if !status.success() {
+            error!("Bisect run failed with {}", status);
+            return Err(anyhow!("Bisect run did not finish ({}); check the test command's output above", status));
+        }
+
+        // Once bisect run succeeds, refs/bisect/bad is the first bad commit.
+        Ok(self.run_git(&["rev-parse", "refs/bisect/bad"], "Bisect result").ok())
+    }
+
+    fn bisect_reset(&self) -> Result<()> {
+        if !self.is_bisecting() {
+            warn!("Not bisecting; nothing to reset");
+            return Ok(());
+        }
+
+        if self.dry_run {
+            self.record(PlanStep::BisectReset);
+            info!("[DRY RUN] Would end the bisect and return to the original branch");
+            return Ok(());
+        }
+
+        self.run_git(&["bisect", "reset"], "Bisect reset")?;
+        Ok(())
+    }
+
+    fn git_config_get(&self, key: &str, global: bool) -> Result<String> {
+        validate_config_key(key)?;
+
+        let mut args = vec!["config"];
+        if global {
+            args.push("--global");
+        }
+        args.extend(["--get", key]);
+
+        let output = self
+            .git_output(&args, false)
+            .map_err(|e| self.spawn_error("Failed to read git config", e))?;
+        if output.success {
+            return Ok(output.stdout.trim_end().to_string());
+        }
+        // A missing key exits non-zero without printing anything.
+        if output.stderr.trim().is_empty() {
+            let scope = if global { " globally" } else { "" };
+            return Err(anyhow!("Git config key {} is not set{}", key, scope));
+        }
+        Err(GitError::CommandFailed { action: String::from("Config read"), stderr: output.stderr }.into())
+    }
+
+    fn git_config_set(&self, key: &str, value: &str, global: bool) -> Result<()> {
+        validate_config_key(key)?;
+        let scope = if global { "global" } else { "local" };
+
+        if self.dry_run {
+            self.record(PlanStep::SetGitConfig { key: key.to_string(), value: value.to_string(), global });
+            info!("[DRY RUN] Would set {} git config {} = {}", scope, key, value);
+            return Ok(());
+        }
+
+        let mut args = vec!["config"];
+        if global {
+            args.push("--global");
+        }
+        args.extend([key, value]);
+        self.run_git(&args, "Config write")?;
+        info!("Set {} git config {} = {}", scope, key, value);
+        Ok(())
+    }
+
+    /// Aggregates `git log --numstat` line by line so large histories are never
+    /// held in memory at once.
+    fn stats(&self, since: Option<&str>) -> Result<RepoStats> {
+        const TOP_FILES: usize = 10;
+
+        if !self.git_succeeds(&["rev-parse", "--verify", "--quiet", "HEAD"]) {
+            return Ok(RepoStats::default());
+        }
+
+        let mut args = vec![String::from("log"), String::from("--numstat"), String::from("--format=%x00%ae")];
+        if let Some(since) = since {
+            args.push(format!("--since={}", since));
+        }
+
+        let mut stats = RepoStats::default();
+        let mut authors = BTreeSet::new();
+        let mut files: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+        self.git_lines(&args, "Log", |line| {
+            if let Some(author) = line.strip_prefix('\0') {
+                stats.commits += 1;
+                authors.insert(author.to_lowercase());
+                return;
+            }
+            let mut fields = line.splitn(3, '\t');
+            let (Some(added), Some(removed), Some(path)) = (fields.next(), fields.next(), fields.next()) else {
+                return;
+            };
+            // Binary files report "-" for both counts.
+            let added = added.parse::<u64>().unwrap_or(0);
+            let removed = removed.parse::<u64>().unwrap_or(0);
+            stats.lines_added += added;
+            stats.lines_removed += removed;
+            let entry = files.entry(path.to_string()).or_default();
+            entry.0 += 1;
+            entry.1 += added + removed;
+        })?;
+
+        stats.contributors = authors.len();
+        let mut churn: Vec<FileChurn> = files
+            .into_iter()
+            .map(|(path, (commits, lines_changed))| FileChurn { path, commits, lines_changed })
+            .collect();
+        churn.sort_by(|a, b| b.lines_changed.cmp(&a.lines_changed).then(b.commits.cmp(&a.commits)));
+        churn.truncate(TOP_FILES);
+        stats.most_changed_files = churn;
+        Ok(stats)
+    }
+
+    fn contributors(&self, since: Option<&str>, until: Option<&str>) -> Result<Vec<(String, usize)>> {
+        if !self.git_succeeds(&["rev-parse", "--verify", "--quiet", "HEAD"]) {
+            return Ok(Vec::new());
+        }
+
+        // An explicit HEAD keeps shortlog from reading a log from stdin.
+        let mut args = vec![String::from("shortlog"), String::from("-sne")];
+        if let Some(since) = since {
+            args.push(format!("--since={}", since));
+        }
+        if let Some(until) = until {
+            args.push(format!("--until={}", until));
+        }
+        args.push(String::from("HEAD"));
+
+        let output = self.run_git(&args, "Contributor list")?;
+        output
+            .lines()
+            .filter_map(|line| line.trim().split_once('\t'))
+            .map(|(count, name)| {
+                let count = count
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| anyhow!("Unexpected shortlog output: {}", count))?;
+                Ok((name.to_string(), count))
+            })
+            .collect()
+    }
+}
+
+fn shell_command(command: &str) -> Command {
+    let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let mut process = Command::new(shell);
+    process.args([flag, command]);
+    process
+}
+
+const OPERATION_LOG_FILE: &str = ".git-automate.log";
+const OPERATION_LOG_MAX_BYTES: u64 = 512 * 1024;
+
+/// Git subcommands that only read repository state and are not worth auditing.
+fn is_query_command<S: AsRef<OsStr>>(args: &[S]) -> bool {
+    let args: Vec<_> = args.iter().map(|arg| arg.as_ref().to_string_lossy()).collect();
+    if args.first().is_some_and(|arg| arg == "-C") {
+        let rest: Vec<&str> = args.iter().skip(2).map(|arg| arg.as_ref()).collect();
+        return is_query_command(&rest);
+    }
+    match args.first().map(|arg| arg.as_ref()) {
+        Some(
+            "rev-parse" | "symbolic-ref" | "rev-list" | "merge-base" | "status" | "diff" | "log" | "shortlog"
+            | "blame" | "ls-files" | "describe" | "for-each-ref" | "ls-remote"
+            | "--version",
+        ) => true,
+        Some("branch") => args.iter().any(|arg| arg.starts_with("--format")),
+        Some("tag") => args.iter().any(|arg| arg == "--list"),
+        Some("stash") => args.get(1).is_some_and(|arg| arg == "list"),
+        Some("remote") => {
+            args.len() == 1 || args[1] == "-v" || (args[1] == "prune" && args.iter().any(|arg| arg == "--dry-run"))
+        }
+        Some("worktree") => args.get(1).is_some_and(|arg| arg == "list"),
+        Some("clean") => args.iter().any(|arg| arg == "-n"),
+        Some("config") => args.len() == 2 || args.iter().any(|arg| arg == "--get"),
+        _ => false,
+    }
+}
+
+/// Appends to the operation log, rotating it to `<name>.1` once it exceeds
+/// `OPERATION_LOG_MAX_BYTES` so it never grows unbounded.
+fn append_operation_log(path: &Path, line: &str) -> io::Result<()> {
+    if fs::metadata(path).is_ok_and(|metadata| metadata.len() >= OPERATION_LOG_MAX_BYTES) {
+        fs::rename(path, path.with_extension("log.1"))?;
+    }
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())
+}
+
+fn find_config_file() -> Option<PathBuf> {
+    let current_dir = std::env::current_dir().ok()?;
+    for dir in current_dir.ancestors() {
+        let candidate = dir.join("git-automate.toml");
+        if candidate.exists() {
+            return Some(candidate);
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    None
+}
+
+/// `$XDG_CONFIG_HOME/git-automate/config.toml`, falling back to `~/.config`.
+fn global_config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .filter(|home| !home.is_empty())
+                .map(|home| Path::new(&home).join(".config"))
+        })?;
+    Some(config_home.join("git-automate").join("config.toml"))
+}
+
+/// The global config supplies defaults and the project file overrides it key by key,
+/// so only the keys a file actually sets take part in the merge.
+fn load_config(path: Option<&Path>, migrate: bool, use_global: bool) -> Result<Config> {
+    let config_path = match path {
+        Some(path) => {
+            if !path.exists() {
+                return Err(anyhow!("Config file not found: {}", path.display()));
+            }
+            Some(path.to_path_buf())
+        }
+        None => find_config_file(),
+    };
+    let global = match global_config_file(use_global, config_path.as_deref()) {
+        Some(global_path) => {
+            info!("Using global config {}", global_path.display());
+            Some(read_config_table(&global_path, migrate)?)
+        }
+        None => None,
+    };
+    let local = config_path.as_deref().map(|config_path| read_config_table(config_path, migrate)).transpose()?;
+
+    let mut config = merge_config_tables(global, local)?;
+    config.apply_env_overrides()?;
+    config.validate().map_err(|e| match &config_path {
+        Some(config_path) => anyhow!("Invalid config file {}:{}", config_path.display(), e),
+        None => anyhow!("Invalid configuration:{}", e),
+    })?;
+
+    Ok(config)
+}
+
+/// The user-wide config to merge under `project`: none with --no-global, when
+/// there is no such file, or when it is the project file itself.
+fn global_config_file(use_global: bool, project: Option<&Path>) -> Option<PathBuf> {
+    if !use_global {
+        return None;
+    }
+    global_config_path()
+        .filter(|global_path| global_path.is_file())
+        .filter(|global_path| project != Some(global_path.as_path()))
+}
+
+/// Layers the project keys over the global ones; keys neither file sets keep their defaults.
+fn merge_config_tables(global: Option<toml::Table>, local: Option<toml::Table>) -> Result<Config> {
+    let mut merged = toml::Table::new();
+    merged.extend(global.into_iter().flatten());
+    merged.extend(local.into_iter().flatten());
+    if merged.is_empty() {
+        return Ok(Config::default());
+    }
+    toml::Value::Table(merged)
+        .try_into()
+        .map_err(|e| anyhow!("Failed to merge config files: {}", e))
+}
+
+/// Parses one config file, warning about unknown keys and old versions (and
+/// migrating it in place when asked), and returns the keys it sets.
+fn read_config_table(config_path: &Path, migrate: bool) -> Result<toml::Table> {
+    let config_str = fs::read_to_string(config_path)
+        .map_err(|e| anyhow!("Failed to read config file {}: {}", config_path.display(), e))?;
+    let mut config: Config = toml::from_str(&config_str)
+        .map_err(|e| anyhow!("Failed to parse config file {}: {}", config_path.display(), e))?;
+    let mut table: toml::Table = toml::from_str(&config_str)
+        .map_err(|e| anyhow!("Failed to parse config file {}: {}", config_path.display(), e))?;
+
+    for key in config.unknown_keys.keys() {
+        warn!("Ignoring unknown key {:?} in config file {}", key, config_path.display());
+        table.remove(key);
+    }
+
+    let old_version = config.version;
+    if config.migrate() {
+        if migrate {
+            fs::write(config_path, toml::to_string_pretty(&config)?)
+                .map_err(|e| anyhow!("Failed to write migrated config {}: {}", config_path.display(), e))?;
+            info!(
+                "Migrated config file {} from version {} to {}",
+                config_path.display(),
+                old_version,
+                config.version
+            );
+        } else {
+            warn!(
+                "Config file {} uses version {}; run with --migrate to upgrade it to version {}",
+                config_path.display(),
+                old_version,
+                CONFIG_VERSION
+            );
+        }
+    } else if migrate {
+        info!("Config file {} is already at version {}", config_path.display(), config.version);
+    }
+    // A file without a version key counts as v1; record the in-memory upgrade so the
+    // merged result does not fall back to v1 either.
+    table.insert(String::from("version"), toml::Value::Integer(i64::from(config.version)));
+
+    Ok(table)
+}
+
+fn parse_env_bool(name: &str, value: &str) -> Result<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" | "" => Ok(false),
+        _ => Err(anyhow!("Invalid boolean value {:?} for {}", value, name)),
+    }
+}
+
+fn clone_destination(url: &str) -> Result<PathBuf> {
+    let name = url
+        .trim_end_matches('/')
+        .rsplit(['/', ':'])
+        .next()
+        .map(|name| name.trim_end_matches(".git"))
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| anyhow!("Cannot derive a destination directory from {}; pass one explicitly", url))?;
+
+    Ok(PathBuf::from(name))
+}
+
+fn prompt(message: &str) -> Result<Option<String>> {
+    print!("{}", message);
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input)? == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(input.trim().to_string()))
+}
+
+/// Asks a yes/no question; without a terminal to ask on this fails rather than
+/// guessing, so scripts have to opt in with -y/--yes.
+fn confirm(message: &str) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "Cannot ask {:?} because stdin is not a terminal; pass -y/--yes to proceed",
+            message
+        ));
+    }
+    let answer = prompt(&format!("{} [y/N] ", message))?.unwrap_or_default();
+    Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Asks until `parse` accepts the answer; an empty answer (or EOF) takes `default`.
+fn prompt_with_default<T>(
+    label: &str,
+    default: &str,
+    parse: impl Fn(&str) -> std::result::Result<T, String>,
+) -> Result<T> {
+    loop {
+        let answer = prompt(&format!("{} [{}]: ", label, default))?;
+        let value = match answer.as_deref() {
+            None => return parse(default).map_err(|e| anyhow!(e)),
+            Some("") => default,
+            Some(value) => value,
+        };
+        match parse(value) {
+            Ok(parsed) => return Ok(parsed),
+            Err(e) => println!("  {}", e),
+        }
+    }
+}
+
+fn parse_yes_no(value: &str) -> std::result::Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "y" | "yes" | "true" => Ok(true),
+        "n" | "no" | "false" => Ok(false),
+        _ => Err(format!("Please answer yes or no (got {:?})", value)),
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value { "yes" } else { "no" }
+}
+
+fn run_init_wizard(git_ops: &GitOps) -> Result<Config> {
+    let mut config = Config::default();
+
+    // Pre-fill the remote from the repository when there is one; `origin` wins if present.
+    let remotes = git_ops.list_remotes().unwrap_or_default();
+    if !remotes.contains(&config.default_remote) {
+        if let Some(remote) = remotes.first() {
+            config.default_remote = remote.clone();
+        }
+    }
+
+    config.default_remote = prompt_with_default("Default remote", &config.default_remote, |value| {
+        if value.split_whitespace().count() == 1 {
+            Ok(value.to_string())
+        } else {
+            Err(format!("Remote name must be a single word (got {:?})", value))
+        }
+    })?;
+    config.commit_template = prompt_with_default("Commit template", &config.commit_template, |value| {
+        if value.contains("{}") {
+            Ok(value.to_string())
+        } else {
+            Err(String::from("Template must contain a {} placeholder for the message"))
+        }
+    })?;
+    config.auto_pull = prompt_with_default("Pull before committing?", yes_no(config.auto_pull), parse_yes_no)?;
+    if config.auto_pull {
+        config.pull_strategy = prompt_with_default(
+            &format!("Pull strategy ({})", PULL_STRATEGIES.join("/")),
+            &config.pull_strategy,
+            |value| {
+                if PULL_STRATEGIES.contains(&value) {
+                    Ok(value.to_string())
+                } else {
+                    Err(format!("Pull strategy must be one of {}", PULL_STRATEGIES.join(", ")))
+                }
+            },
+        )?;
+    }
+    config.protected_branches = prompt_with_default(
+        "Protected branches (comma-separated)",
+        &config.protected_branches.join(","),
+        |value| {
+            Ok(value
+                .split(',')
+                .map(str::trim)
+                .filter(|branch| !branch.is_empty())
+                .map(String::from)
+                .collect())
+        },
+    )?;
+    config.branch_prefix = prompt_with_default("Branch prefix (\"-\" for none)", "-", |value| {
+        Ok(Some(value.to_string()).filter(|prefix| prefix != "-"))
+    })?;
+    config.push_tags = prompt_with_default("Push tags with each push?", yes_no(config.push_tags), parse_yes_no)?;
+
+    config.validate().map_err(|e| anyhow!("Invalid configuration: {}", e))?;
+    Ok(config)
+}
+
+fn select_branch(branches: &[String], current_branch: Option<&str>) -> Result<Option<String>> {
+    if branches.is_empty() {
+        return Err(anyhow!("No branches to switch to"));
+    }
+
+    for (index, branch) in branches.iter().enumerate() {
+        let marker = if current_branch == Some(branch.as_str()) { "*" } else { " " };
+        println!("{} {:>2}) {}", marker, index + 1, branch);
+    }
+
+    let input = match prompt("Select a branch: ")? {
+        Some(input) if !input.is_empty() => input,
+        _ => return Ok(None),
+    };
+
+    match input.parse::<usize>() {
+        Ok(choice) if (1..=branches.len()).contains(&choice) => Ok(Some(branches[choice - 1].clone())),
+        _ => Err(anyhow!("Invalid selection: {}", input)),
+    }
+}
+
+fn parse_fetch_updates(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (_, target) = line.split_once(" -> ")?;
+            let target = target.split_whitespace().next()?;
+            let kind = if line.contains("[new branch]") {
+                "new branch"
+            } else if line.contains("[new tag]") {
+                "new tag"
+            } else if line.contains("[new ref]") {
+                "new ref"
+            } else if line.contains("[deleted]") {
+                "deleted"
+            } else if line.contains("forced update") {
+                "forced update"
+            } else {
+                "updated"
+            };
+            Some(format!("{}: {}", kind, target))
+        })
+        .collect()
+}
+
+fn validate_blame_range(range: &str) -> Result<()> {
+    let invalid = || anyhow!("Invalid line range {:?}: expected START,END or START,+COUNT", range);
+    let (start, end) = range.split_once(',').ok_or_else(invalid)?;
+    let start = start.trim().parse::<usize>().map_err(|_| invalid())?;
+    if start == 0 {
+        return Err(anyhow!("Invalid line range {:?}: lines are numbered from 1", range));
+    }
+
+    let end = end.trim();
+    if let Some(count) = end.strip_prefix('+') {
+        if count.parse::<usize>().map_err(|_| invalid())? == 0 {
+            return Err(anyhow!("Invalid line range {:?}: the count must be at least 1", range));
+        }
+    } else if end.parse::<usize>().map_err(|_| invalid())? < start {
+        return Err(anyhow!("Invalid line range {:?}: the end is before the start", range));
+    }
+    Ok(())
+}
+
+/// Parses `git blame --line-porcelain` output, where every line carries its
+/// own header block followed by the tab-prefixed source line.
+fn parse_blame_porcelain(output: &str) -> Vec<BlameLine> {
+    let mut lines = Vec::new();
+    let (mut sha, mut line, mut author, mut time, mut tz) = (String::new(), 0, String::new(), 0, String::new());
+
+    for row in output.lines() {
+        if row.starts_with('\t') {
+            lines.push(BlameLine { line, author: author.clone(), sha: sha.clone(), date: blame_date(time, &tz) });
+        } else if let Some(value) = row.strip_prefix("author ") {
+            author = value.to_string();
+        } else if let Some(value) = row.strip_prefix("author-time ") {
+            time = value.parse().unwrap_or(0);
+        } else if let Some(value) = row.strip_prefix("author-tz ") {
+            tz = value.to_string();
+        } else {
+            let mut fields = row.split(' ');
+            if let (Some(hash), Some(_), Some(final_line)) = (fields.next(), fields.next(), fields.next()) {
+                if hash.len() >= 40 && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                    sha = hash.to_string();
+                    line = final_line.parse().unwrap_or(0);
+                }
+            }
+        }
+    }
+    lines
+}
+
+fn blame_date(timestamp: i64, tz: &str) -> String {
+    let offset = tz
+        .get(1..)
+        .and_then(|digits| {
+            let hours = digits.get(..2)?.parse::<i32>().ok()?;
+            let minutes = digits.get(2..4)?.parse::<i32>().ok()?;
+            let seconds = hours * 3600 + minutes * 60;
+            chrono::FixedOffset::east_opt(if tz.starts_with('-') { -seconds } else { seconds })
+        })
+        .unwrap_or_else(|| chrono::FixedOffset::east_opt(0).expect("zero offset is valid"));
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|date| date.with_timezone(&offset).to_rfc3339())
+        .unwrap_or_default()
+}
+
+/// Runs `operation`, retrying network failures up to `retries` times with an
+/// exponential backoff (1s, 2s, 4s, ... capped at 32s) handed to `sleep`.
+fn retry_network_errors<T>(
+    action: &str,
+    retries: u32,
+    mut operation: impl FnMut() -> Result<T>,
+    mut sleep: impl FnMut(Duration),
+) -> Result<T> {
+    let mut attempt = 0;
+    loop {
+        match operation() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < retries && is_network_error(&e.to_string()) => {
+                attempt += 1;
+                let delay = Duration::from_secs(1 << (attempt - 1).min(5));
+                warn!(
+                    "Retrying {} (attempt {}/{}) in {}s after network error",
+                    action,
+                    attempt,
+                    retries,
+                    delay.as_secs()
+                );
+                sleep(delay);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn is_network_error(message: &str) -> bool {
+    const NETWORK_ERRORS: &[&str] = &[
+        "connection reset",
+        "connection refused",
+        "connection timed out",
+        "operation timed out",
+        "could not read from remote",
+        "could not resolve host",
+        "the remote end hung up",
+        "early eof",
+        "unable to access",
+    ];
+    // "unable to access" and "could not read from remote" also wrap credential
+    // problems, which retrying only repeats.
+    const AUTH_ERRORS: &[&str] = &[
+        "authentication failed",
+        "could not read username",
+        "could not read password",
+        "permission denied",
+        "returned error: 401",
+        "returned error: 403",
+    ];
+
+    let message = message.to_lowercase();
+    NETWORK_ERRORS.iter().any(|pattern| message.contains(pattern))
+        && !AUTH_ERRORS.iter().any(|pattern| message.contains(pattern))
+}
+
+fn validate_branch_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(anyhow!("Branch name cannot be empty"));
+    }
+    if name.starts_with('-') {
+        return Err(anyhow!("Invalid branch name {:?}: cannot start with '-'", name));
+    }
+    if name.starts_with('/') || name.ends_with('/') {
+        return Err(anyhow!("Invalid branch name {:?}: cannot start or end with '/'", name));
+    }
+    if let Some(sequence) = ["..", "@{", "//"].into_iter().find(|sequence| name.contains(sequence)) {
+        return Err(anyhow!("Invalid branch name {:?}: cannot contain '{}'", name, sequence));
+    }
+    if name == "@" || name.ends_with('.') {
+        return Err(anyhow!("Invalid branch name {:?}: cannot be '@' or end with '.'", name));
+    }
+    if name.split('/').any(|part| part.starts_with('.') || part.ends_with(".lock")) {
+        return Err(anyhow!("Invalid branch name {:?}: no part may start with '.' or end with '.lock'", name));
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| c.is_whitespace() || c.is_control() || matches!(c, '~' | '^' | ':' | '?' | '*' | '[' | '\\'))
+    {
+        return Err(anyhow!("Invalid branch name {:?}: cannot contain {:?}", name, c));
+    }
+
+    Ok(())
+}
+
+/// Accepts `section.key` and `section.subsection.key`, the forms git itself takes.
+fn validate_config_key(key: &str) -> Result<()> {
+    let is_name = |part: &str| {
+        part.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+            && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    let valid = match (key.split_once('.'), key.rsplit_once('.')) {
+        (Some((section, _)), Some((middle, name))) => {
+            let subsection = middle.split_once('.').map(|(_, subsection)| subsection);
+            is_name(section) && is_name(name) && subsection.is_none_or(|s| !s.is_empty() && !s.contains('\n'))
+        }
+        _ => false,
+    };
+    if !valid {
+        return Err(anyhow!("Invalid git config key {:?}: expected section.key (e.g. user.email)", key));
+    }
+    Ok(())
+}
+
+fn parse_first_bad_commit(output: &str) -> Option<String> {
+    output.lines().find_map(|line| line.strip_suffix(" is the first bad commit")).map(String::from)
+}
+
+fn print_bisect_progress(output: &str) {
+    match parse_first_bad_commit(output) {
+        Some(sha) => {
+            println!("{} {}", paint("First bad commit:", Color::Red), sha);
+            println!("Run `git-automate bisect reset` to finish");
+        }
+        None if !output.is_empty() => println!("{}", output),
+        None => {}
+    }
+}
+
+/// `git commit --amend` with a new message, or keeping the old one (`--no-edit`) when there is none.
+fn amend_args(message: Option<&str>) -> Vec<String> {
+    let mut args = vec![String::from("commit"), String::from("--amend")];
+    match message {
+        Some(message) => args.extend([String::from("-m"), message.to_string()]),
+        None => args.push(String::from("--no-edit")),
+    }
+    args
+}
+
+fn is_protected_branch(branch: &str, protected_branches: &[String]) -> bool {
+    protected_branches.iter().any(|protected| protected == branch)
+}
+
+const CONVENTIONAL_TYPES: &[&str] = &[
+    "feat", "fix", "chore", "docs", "refactor", "test", "perf", "build", "ci", "style", "revert",
+];
+
+struct ConventionalHeader<'a> {
+    kind: &'a str,
+    scope: Option<&'a str>,
+    breaking: bool,
+    description: &'a str,
+}
+
+fn parse_conventional(subject: &str) -> Option<ConventionalHeader<'_>> {
+    let (prefix, description) = subject.split_once(':')?;
+    let (prefix, breaking) = match prefix.strip_suffix('!') {
+        Some(prefix) => (prefix, true),
+        None => (prefix, false),
+    };
+    let (kind, scope) = match prefix.split_once('(') {
+        Some((kind, scope)) => (kind, Some(scope.strip_suffix(')')?)),
+        None => (prefix, None),
+    };
+
+    if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_lowercase()) {
+        return None;
+    }
+
+    Some(ConventionalHeader { kind, scope, breaking, description: description.trim_start() })
+}
+
+const CHANGELOG_SECTIONS: &[(&str, &str)] = &[
+    ("feat", "Features"),
+    ("fix", "Fixes"),
+    ("perf", "Performance"),
+    ("refactor", "Refactoring"),
+    ("docs", "Documentation"),
+    ("test", "Tests"),
+    ("build", "Build"),
+    ("ci", "CI"),
+    ("style", "Style"),
+    ("chore", "Chores"),
+    ("revert", "Reverts"),
+];
+
+/// Renders commits as Markdown grouped by conventional-commit type. Breaking
+/// changes are listed first and anything unconventional ends up under "Other".
+fn render_changelog(title: &str, commits: &[(String, String)]) -> String {
+    let mut breaking = Vec::new();
+    let mut sections: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+
+    for (sha, subject) in commits {
+        let conventional = parse_conventional(subject)
+            .and_then(|header| Some((CHANGELOG_SECTIONS.iter().position(|(kind, _)| *kind == header.kind)?, header)));
+        let (index, entry) = match conventional {
+            Some((index, header)) => {
+                let entry = match header.scope {
+                    Some(scope) => format!("**{}:** {} ({})", scope, header.description, sha),
+                    None => format!("{} ({})", header.description, sha),
+                };
+                if header.breaking {
+                    breaking.push(entry.clone());
+                }
+                (index, entry)
+            }
+            None => (CHANGELOG_SECTIONS.len(), format!("{} ({})", subject, sha)),
+        };
+        sections.entry(index).or_default().push(entry);
+    }
+
+    let mut changelog = format!("# {}\n", title);
+    if commits.is_empty() {
+        changelog.push_str("\nNo changes.\n");
+    }
+    let breaking_section = (!breaking.is_empty()).then_some(("Breaking Changes", &breaking));
+    let grouped = sections.iter().map(|(index, entries)| {
+        let heading = CHANGELOG_SECTIONS.get(*index).map(|(_, heading)| *heading).unwrap_or("Other");
+        (heading, entries)
+    });
+    for (heading, entries) in breaking_section.into_iter().chain(grouped) {
+        changelog.push_str(&format!("\n## {}\n\n", heading));
+        for entry in entries {
+            changelog.push_str(&format!("- {}\n", entry));
+        }
+    }
+    changelog
+}
+
+struct ConventionalOptions {
+    commit_type: Option<String>,
+    scope: Option<String>,
+    breaking: bool,
+}
+
+impl ConventionalOptions {
+    fn apply(&self, message: &str) -> String {
+        let (subject, body) = match message.split_once('\n') {
+            Some((subject, body)) => (subject, Some(body)),
+            None => (message, None),
+        };
+        let existing = parse_conventional(subject);
+
+        let kind = self.commit_type.as_deref().or(existing.as_ref().map(|h| h.kind)).unwrap_or("feat");
+        let scope = self.scope.as_deref().or(existing.as_ref().and_then(|h| h.scope));
+        let breaking = self.breaking || existing.as_ref().is_some_and(|h| h.breaking);
+        let description = existing.as_ref().map(|h| h.description).unwrap_or(subject);
+
+        let mut header = String::from(kind);
+        if let Some(scope) = scope {
+            header.push_str(&format!("({})", scope));
+        }
+        if breaking {
+            header.push('!');
+        }
+
+        match body {
+            Some(body) => format!("{}: {}\n{}", header, description, body),
+            None => format!("{}: {}", header, description),
+        }
+    }
+}
+
+/// Joins the expanded branch prefix, the `--type` segment and the name. Only a
+/// slash in the name itself marks it as already namespaced and drops the prefix.
+fn compose_branch_name(prefix: Option<&str>, branch_type: Option<&str>, name: &str) -> String {
+    let prefix = prefix.filter(|_| !name.contains('/')).unwrap_or_default();
+    match branch_type {
+        Some(branch_type) => format!("{}{}/{}", prefix, branch_type, name),
+        None => format!("{}{}", prefix, name),
+    }
+}
+
+/// Lowercases `text` and joins its runs of letters and digits (Unicode included)
+/// with single hyphens, dropping separators at either end.
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+fn validate_commit_type(commit_type: &str, extra_types: &[String]) -> Result<()> {
+    if CONVENTIONAL_TYPES.contains(&commit_type) || extra_types.iter().any(|t| t == commit_type) {
+        return Ok(());
+    }
+
+    let mut allowed: Vec<&str> = CONVENTIONAL_TYPES.to_vec();
+    allowed.extend(extra_types.iter().map(String::as_str));
+    Err(anyhow!("Unknown commit type {:?}; expected one of {}", commit_type, allowed.join(", ")))
+}
+
+fn lint_commit_message(message: &str, config: &Config) -> Result<()> {
+    let subject = message.lines().next().unwrap_or_default();
+
+    if let Some(max) = config.max_subject_length {
+        let length = subject.chars().count();
+        if length > max {
+            return Err(anyhow!(
+                "Commit subject is {} characters, over max_subject_length ({}): {:?}; shorten it or pass --no-lint",
+                length,
+                max,
+                subject
+            ));
+        }
+    }
+
+    if message.lines().nth(1).is_some_and(|line| !line.trim().is_empty()) {
+        return Err(anyhow!(
+            "Commit message needs a blank line between the subject and the body; fix it or pass --no-lint"
+        ));
+    }
+
+    if config.forbid_subject_period && subject.trim_end().ends_with('.') {
+        return Err(anyhow!(
+            "Commit subject {:?} ends with a period (forbid_subject_period); drop it or pass --no-lint",
+            subject
+        ));
+    }
+
+    if config.require_conventional {
+        let header = parse_conventional(subject).ok_or_else(|| {
+            anyhow!(
+                "Commit subject {:?} is not a conventional commit (type(scope): description); pass --no-lint to skip",
+                subject
+            )
+        })?;
+        validate_commit_type(header.kind, &config.extra_commit_types)?;
+        if header.description.trim().is_empty() {
+            return Err(anyhow!("Commit subject {:?} has no description after the type", subject));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the `Name <email>` form git expects for authors and co-author trailers.
+fn validate_identity(kind: &str, identity: &str) -> Result<()> {
+    let invalid = || anyhow!("Invalid {} {:?}; expected \"Name <email>\"", kind, identity);
+
+    let (name, email) = identity
+        .trim()
+        .strip_suffix('>')
+        .and_then(|rest| rest.rsplit_once(" <"))
+        .ok_or_else(invalid)?;
+    let valid_name = !name.trim().is_empty() && !name.contains(['<', '>']);
+    let valid_email = email
+        .split_once('@')
+        .is_some_and(|(user, domain)| !user.is_empty() && !domain.is_empty())
+        && !email.contains(|c: char| c.is_whitespace() || c == '<' || c == '>');
+
+    if valid_name && valid_email { Ok(()) } else { Err(invalid()) }
+}
+
+fn is_trailer_line(line: &str) -> bool {
+    line.split_once(": ")
+        .is_some_and(|(key, _)| !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+}
+
+/// Appends trailer lines, joining an existing trailer block or starting a new one after a blank line.
+fn append_trailers(message: &str, trailers: &[String]) -> String {
+    let trailers: Vec<&str> = trailers
+        .iter()
+        .map(String::as_str)
+        .filter(|trailer| !message.contains(trailer))
+        .collect();
+    if trailers.is_empty() {
+        return message.to_string();
+    }
+
+    let message = message.trim_end();
+    let has_trailer_block = message
+        .rsplit_once("\n\n")
+        .is_some_and(|(_, last)| last.lines().all(is_trailer_line));
+    let separator = if has_trailer_block { "\n" } else { "\n\n" };
+    format!("{}{}{}", message, separator, trailers.join("\n"))
+}
+
+fn append_co_authors(message: &str, co_authors: &[String]) -> String {
+    let trailers: Vec<String> = co_authors
+        .iter()
+        .map(|co_author| format!("Co-authored-by: {}", co_author.trim()))
+        .collect();
+    append_trailers(message, &trailers)
+}
+
+/// Returns the first capture group of `pattern` in `branch`, or the whole match when it has none.
+fn extract_ticket(pattern: &Regex, branch: &str) -> Option<String> {
+    let captures = pattern.captures(branch)?;
+    captures.get(1).or_else(|| captures.get(0)).map(|m| m.as_str().to_string())
+}
+
+fn apply_ticket(message: &str, ticket: &str, placement: &str) -> String {
+    // Only a whole-word mention counts, so ABC-1 is still added to a message about ABC-12.
+    let mentioned = message.match_indices(ticket).any(|(start, _)| {
+        let before = message[..start].chars().next_back();
+        let after = message[start + ticket.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    });
+    if mentioned {
+        return message.to_string();
+    }
+
+    let (subject, body) = match message.split_once('\n') {
+        Some((subject, body)) => (subject, Some(body)),
+        None => (message, None),
+    };
+    let subject = match placement {
+        "prefix" => format!("{}: {}", ticket, subject),
+        "suffix" => format!("{} ({})", subject, ticket),
+        _ => return append_trailers(message, &[format!("Refs: {}", ticket)]),
+    };
+    match body {
+        Some(body) => format!("{}\n{}", subject, body),
+        None => subject,
+    }
+}
+
+/// Fills the template with `subject`, or with a random name when there is none.
+fn generate_commit_message(template: &str, subject: Option<&str>, conventional: Option<&ConventionalOptions>) -> String {
+    let name = match subject {
+        Some(subject) => subject.to_string(),
+        None => Generator::default().next().unwrap(),
+    };
+
+    let message = if template.contains("{}") {
+        template.replacen("{}", &name, 1)
+    } else {
+        warn!("Commit template {:?} has no {{}} placeholder; appending generated name", template);
+        format!("{} {}", template, name).trim().to_string()
+    };
+
+    match conventional {
+        Some(conventional) => conventional.apply(&message),
+        None => message,
+    }
+}
+
+/// Builds a short description such as "update 3 files in src/" from `git diff --stat`
+/// output. Returns `None` when no files are listed.
+fn describe_diff_stat(stat: &str) -> Option<String> {
+    let paths: Vec<String> = stat
+        .lines()
+        .filter_map(|line| line.rsplit_once(" | "))
+        .map(|(path, _)| diff_stat_path(path.trim()))
+        .collect();
+
+    match paths.as_slice() {
+        [] => return None,
+        [path] => return Some(format!("update {}", path)),
+        _ => {}
+    }
+    let count = paths.len();
+
+    let dirs: Vec<Vec<&str>> = paths
+        .iter()
+        .map(|path| {
+            let mut parts: Vec<&str> = path.split('/').collect();
+            parts.pop();
+            parts
+        })
+        .collect();
+    let common: Vec<&str> = dirs[0]
+        .iter()
+        .enumerate()
+        .take_while(|(index, part)| dirs.iter().all(|dir| dir.get(*index) == Some(*part)))
+        .map(|(_, part)| *part)
+        .collect();
+    if !common.is_empty() {
+        return Some(format!("update {} files in {}/", count, common.join("/")));
+    }
+
+    let extension = |path: &str| Path::new(path).extension().map(|ext| ext.to_string_lossy().into_owned());
+    let first_extension = extension(&paths[0]);
+    if first_extension.is_some() && paths.iter().all(|path| extension(path) == first_extension) {
+        return Some(format!("update {} .{} files", count, first_extension.unwrap_or_default()));
+    }
+
+    let mut top_level: BTreeMap<&str, usize> = BTreeMap::new();
+    for dir in dirs.iter().filter_map(|dir| dir.first()) {
+        *top_level.entry(dir).or_default() += 1;
+    }
+    match top_level.into_iter().max_by_key(|(_, files)| *files) {
+        Some((dir, files)) if files * 2 > count => Some(format!("update {} files, mostly in {}/", count, dir)),
+        _ => Some(format!("update {} files", count)),
+    }
+}
+
+/// Resolves the rename notations `old => new` and `dir/{old => new}/file` to the new path.
+fn diff_stat_path(path: &str) -> String {
+    match (path.find('{'), path.find('}')) {
+        (Some(open), Some(close)) if open < close => {
+            let inner = &path[open + 1..close];
+            let new = inner.split_once(" => ").map_or(inner, |(_, new)| new);
+            format!("{}{}{}", &path[..open], new, &path[close + 1..]).replace("//", "/")
+        }
+        _ => path.split_once(" => ").map_or(path, |(_, new)| new).to_string(),
+    }
+}
+
+fn read_message_file(path: &Path) -> Result<String> {
+    if !path.is_file() {
+        return Err(anyhow!("Message file not found: {}", path.display()));
+    }
+
+    let message = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read message file {}: {}", path.display(), e))?;
+    let message = message.trim_end();
+    if message.trim().is_empty() {
+        return Err(anyhow!("Message file {} is empty", path.display()));
+    }
+
+    Ok(message.to_string())
+}
+
+fn check_commit_exclude(git_ops: &GitOps, files: &[String], update_only: bool) -> Result<()> {
+    let patterns: Vec<(&str, Regex)> = git_ops
+        .config
+        .commit_exclude
+        .iter()
+        .map(|pattern| Ok((pattern.as_str(), glob_to_regex(pattern)?)))
+        .collect::<Result<_>>()?;
+    let excluded: Vec<String> = git_ops
+        .files_to_stage(files, update_only)?
+        .into_iter()
+        .filter_map(|path| {
+            let (pattern, _) = patterns.iter().find(|(_, regex)| regex.is_match(&path))?;
+            Some(format!("{} (matches {:?})", path, pattern))
+        })
+        .collect();
+    if excluded.is_empty() {
+        return Ok(());
+    }
+
+    let listing = excluded.join("\n  ");
+    if git_ops.config.commit_exclude_action == "warn" {
+        if !quiet() {
+            eprintln!("Warning: staging files that match commit_exclude:\n  {}", listing);
+        }
+        return Ok(());
+    }
+    if git_ops.dry_run {
+        warn!("[DRY RUN] These files match commit_exclude; a real run would abort:\n  {}", listing);
+        return Ok(());
+    }
+
+    error!("Refusing to stage files that match commit_exclude");
+    Err(anyhow!(
+        "Refusing to stage files that match commit_exclude:\n  {}\nRemove them, narrow --files, or pass --allow-excluded",
+        listing
+    ))
+}
+
+/// Translates a gitignore-style glob into a regex over repository-relative paths.
+/// `*`, `?` and `[...]` stay within one path component, `**` crosses them and `\`
+/// makes the next character literal. A pattern without a slash matches at any
+/// depth, and matching a directory covers its contents.
+fn glob_to_regex(pattern: &str) -> Result<Regex> {
+    let trimmed = pattern.trim().trim_end_matches('/');
+    let anchored = trimmed.contains('/');
+    let trimmed = trimmed.trim_start_matches('/');
+
+    let mut regex = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    let mut chars = trimmed.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => match glob_class(&mut chars) {
+                Some(class) => regex.push_str(&class),
+                None => regex.push_str(r"\["),
+            },
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    regex.push_str(&regex::escape(&c.to_string()));
+                }
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push_str("(?:/.*)?$");
+
+    Regex::new(&regex).map_err(|e| anyhow!("Invalid commit_exclude pattern {:?}: {}", pattern, e))
+}
+
+/// Turns the rest of a `[...]` glob class (`[abc]`, `[a-z]`, `[!abc]`) into a
+/// regex class, or `None` when it is never closed and the `[` is literal.
+fn glob_class(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Option<String> {
+    let mut lookahead = chars.clone();
+    let mut class = String::from("[");
+    if matches!(lookahead.peek(), Some('!' | '^')) {
+        lookahead.next();
+        class.push_str("^/");
+    }
+
+    let mut first = true;
+    loop {
+        match lookahead.next()? {
+            // A `]` right after the opening bracket is a member, not the end.
+            ']' if !first => break,
+            '-' if !first && lookahead.peek().is_some_and(|next| *next != ']') => class.push('-'),
+            c @ ('\\' | '[' | ']' | '^' | '&' | '~' | '-') => {
+                class.push('\\');
+                class.push(c);
+            }
+            c => class.push(c),
+        }
+        first = false;
+    }
+    class.push(']');
+
+    *chars = lookahead;
+    Some(class)
+}
+
+fn confirm_staging_all(git_ops: &GitOps, yes: bool, update_only: bool) -> Result<bool> {
+    let entries: Vec<_> = git_ops
+        .status_entries()?
+        .into_iter()
+        .filter(|entry| entry.unstaged != Some(FileState::Ignored))
+        .filter(|entry| !update_only || entry.unstaged != Some(FileState::Untracked))
+        .collect();
+    if entries.is_empty() || git_ops.plan.is_some() {
+        return Ok(true);
+    }
+
+    let prompting = !yes && !git_ops.dry_run && io::stdin().is_terminal();
+    if quiet() && !prompting {
+        return Ok(true);
+    }
+
+    println!("Files to be committed:");
+    for entry in &entries {
+        let state = entry.unstaged.or(entry.staged).map(FileState::label).unwrap_or("changed");
+        println!("  {:<10} {}", state, entry.display_path());
+    }
+
+    if !prompting {
+        return Ok(true);
+    }
+
+    confirm("Stage and commit these files?")
+}
+
+fn preview_staged(git_ops: &GitOps, yes: bool, no_pager: bool) -> Result<bool> {
+    if git_ops.plan.is_some() {
+        return Ok(true);
+    }
+
+    let diff = if git_ops.dry_run {
+        // Nothing was staged, so show what staging would have picked up.
+        let staged = git_ops.diff(true, false)?;
+        let unstaged = git_ops.diff(false, false)?;
+        [staged, unstaged].into_iter().filter(|diff| !diff.is_empty()).collect::<Vec<_>>().join("\n")
+    } else {
+        git_ops.diff(true, false)?
+    };
+    if diff.is_empty() {
+        return Ok(true);
+    }
+    print_paged(&diff, no_pager)?;
+
+    if yes || git_ops.dry_run || !io::stdin().is_terminal() {
+        return Ok(true);
+    }
+    confirm("Commit these changes?")
+}
+
+/// Whether `commit` pulls first, and why: --pull and --no-pull override auto_pull.
+fn pull_decision(auto_pull: bool, pull: bool, no_pull: bool) -> (bool, &'static str) {
+    if pull {
+        (true, "--pull")
+    } else if no_pull {
+        (false, "--no-pull")
+    } else if auto_pull {
+        (true, "auto_pull = true")
+    } else {
+        (false, "auto_pull = false")
+    }
+}
+
+/// What `commit` does when there is nothing to commit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EmptyCommit {
+    Skip,
+    Fail,
+    Create,
+}
+
+/// --require-changes and --allow-empty take precedence over empty_commit_behavior.
+fn empty_commit_outcome(behavior: &str, allow_empty: bool, require_changes: bool) -> EmptyCommit {
+    if require_changes {
+        EmptyCommit::Fail
+    } else if allow_empty || behavior == "allow-empty" {
+        EmptyCommit::Create
+    } else if behavior == "error" {
+        EmptyCommit::Fail
+    } else {
+        EmptyCommit::Skip
+    }
+}
+
+/// What `commit` (with or without `--onto`) works out from its flags and the
+/// config before touching the repository.
+struct CommitSettings {
+    options: CommitOptions,
+    conventional: Option<ConventionalOptions>,
+    co_authors: Vec<String>,
+    /// The -m or -F message with any conventional header applied.
+    message: Option<String>,
+}
+
+impl CommitSettings {
+    fn resolve(git_ops: &GitOps, args: &CommitArgs) -> Result<Self> {
+        if let Some(commit_type) = &args.commit_type {
+            validate_commit_type(commit_type, &git_ops.config.extra_commit_types)?;
+        }
+        if let Some(author) = &args.author {
+            validate_identity("author", author)?;
+        }
+        let mut co_authors = git_ops.config.co_authors.clone();
+        for co_author in &args.co_authors {
+            validate_identity("co-author", co_author)?;
+            if !co_authors.contains(co_author) {
+                co_authors.push(co_author.clone());
+            }
+        }
+        let conventional = (args.conventional || args.commit_type.is_some() || args.scope.is_some() || args.breaking)
+            .then(|| ConventionalOptions {
+                commit_type: args.commit_type.clone(),
+                scope: args.scope.clone(),
+                breaking: args.breaking,
+            });
+
+        let message = match (&args.message, &args.message_file) {
+            (Some(message), _) => Some(message.clone()),
+            (None, Some(path)) => Some(read_message_file(path)?),
+            (None, None) => None,
+        };
+        let message = match (message, &conventional) {
+            (Some(message), Some(conventional)) => Some(conventional.apply(&message)),
+            (message, _) => message,
+        };
+
+        let options = CommitOptions {
+            no_verify: args.no_verify,
+            allow_empty: false,
+            sign: if args.sign {
+                true
+            } else if args.no_sign {
+                false
+            } else {
+                git_ops.config.sign_commits
+            },
+            author: args.author.clone(),
+            sign_off: git_ops.sign_off_identity(args.signoff)?,
+        };
+
+        Ok(Self { options, conventional, co_authors, message })
+    }
+}
+
+/// The ticket referenced by `branch`, or an error when `require_ticket` is set and there is none.
+fn branch_ticket(config: &Config, branch: &str) -> Result<Option<String>> {
+    let Some(pattern) = &config.ticket_pattern else {
+        return Ok(None);
+    };
+    let regex = Regex::new(pattern).map_err(|e| anyhow!("Invalid ticket_pattern {:?}: {}", pattern, e))?;
+    let ticket = extract_ticket(&regex, branch);
+    if ticket.is_none() && config.require_ticket {
+        error!("Branch {} does not reference a ticket", branch);
+        return Err(anyhow!(
+            "Branch {} does not match ticket_pattern {:?}; rename it to include a ticket or unset require_ticket",
+            branch,
+            pattern
+        ));
+    }
+    Ok(ticket)
+}
+
+fn decorate_commit_message(config: &Config, message: &str, ticket: Option<&str>, co_authors: &[String]) -> String {
+    let message = match ticket {
+        Some(ticket) => apply_ticket(message, ticket, &config.ticket_placement),
+        None => message.to_string(),
+    };
+    append_co_authors(&message, co_authors)
+}
+
+/// Carries the working tree over to `onto` (stash, switch, pop), commits and
+/// pushes there, then switches back the same way. This is best-effort: any
+/// conflict while carrying changes stops the run with the stash kept.
+fn run_commit_onto(git_ops: &GitOps, args: &CommitArgs, yes: bool, onto: &str) -> Result<()> {
+    if !git_ops.git_succeeds(&["rev-parse", "--verify", "--quiet", &format!("refs/heads/{}", onto)]) {
+        return Err(anyhow!("Cannot commit onto {}: no such local branch", onto));
+    }
+    let original = git_ops.get_current_branch()?;
+    if original == onto {
+        return Err(anyhow!("Already on {}; drop --onto to commit here", onto));
+    }
+
+    if git_ops.dry_run {
+        if git_ops.plan.is_none() {
+            println!("[DRY RUN] Commit plan for {} (from {}):", onto, original);
+            println!("  1. stash changes and switch to {}", onto);
+            println!("  2. re-apply the changes, stage and commit them");
+            println!("  3. push {} to {}", onto, git_ops.remote());
+            println!("  4. switch back to {} and re-apply anything left over", original);
+        }
+        let CommitSettings { options, conventional, co_authors, message } = CommitSettings::resolve(git_ops, args)?;
+        // The commit is made on `onto`, so that is the branch the ticket comes from.
+        let ticket = branch_ticket(&git_ops.config, onto)?;
+        let message = message.unwrap_or_else(|| {
+            generate_commit_message(&git_ops.config.commit_template, None, conventional.as_ref())
+        });
+        let message = decorate_commit_message(&git_ops.config, &message, ticket.as_deref(), &co_authors);
+        let files = args.files.clone().unwrap_or_else(|| vec![String::from(".")]);
+        git_ops.switch_branch(onto, true)?;
+        git_ops.add_files(&files, args.update_only)?;
+        git_ops.commit(&message, &options)?;
+        git_ops.push(onto, ForceMode::None, args.no_verify_push)?;
+        git_ops.switch_branch(&original, true)?;
+        return Ok(());
+    }
+
+    git_ops.switch_branch(onto, true)?;
+    let result = run_commit(git_ops, &CommitArgs { onto: None, ..args.clone() }, yes);
+    let conflicted = result.as_ref().is_err_and(|e| {
+        e.chain().any(|cause| matches!(cause.downcast_ref::<GitError>(), Some(GitError::MergeConflict { .. })))
+    });
+    if conflicted {
+        // Switching back would fail on the unresolved files; leave them for the user.
+        return result.map_err(|e| {
+            e.context(format!("Stopped on {} with conflicts; resolve them before returning to {}", onto, original))
+        });
+    }
+
+    let restored = git_ops.switch_branch(&original, true);
+
+    match (result, restored) {
+        (Ok(()), Ok(())) => {
+            info!("Committed onto {} and switched back to {}", onto, original);
+            Ok(())
+        }
+        (Ok(()), Err(e)) => Err(e.context(format!("Committed onto {} but could not switch back to {}", onto, original))),
+        (Err(e), Ok(())) => Err(e.context(format!("Committing onto {} failed; switched back to {}", onto, original))),
+        (Err(e), Err(restore_error)) => Err(e.context(format!(
+            "Committing onto {} failed and switching back to {} also failed ({}); you are still on {}",
+            onto, original, restore_error, onto
+        ))),
+    }
+}
+
+fn run_commit(git_ops: &GitOps, args: &CommitArgs, yes: bool) -> Result<()> {
+    if let Some(onto) = &args.onto {
+        return run_commit_onto(git_ops, args, yes, onto);
+    }
+
+    let force_mode = if args.force_unsafe {
+        ForceMode::Unsafe
+    } else if args.force {
+        ForceMode::WithLease
+    } else {
+        ForceMode::None
+    };
+
+    // Resolved up front so a bad flag or -F file fails before anything is pulled or staged.
+    let CommitSettings { options: mut commit_options, conventional, co_authors, message } =
+        CommitSettings::resolve(git_ops, args)?;
+
+    let when_empty = empty_commit_outcome(&git_ops.config.empty_commit_behavior, args.allow_empty, args.require_changes);
+
+    if let Some(remote) = &args.upstream_remote {
+        git_ops.ensure_remote_exists(remote)?;
+    }
+    if let Some(upstream_branch) = &args.upstream_branch {
+        validate_branch_name(upstream_branch)?;
+    }
+
+    let branch = git_ops.get_current_branch()?;
+    let upstream = (args.upstream_remote.is_some() || args.upstream_branch.is_some()).then(|| {
+        (
+            args.upstream_remote.as_deref().unwrap_or(git_ops.remote()),
+            args.upstream_branch.as_deref().unwrap_or(&branch),
+        )
+    });
+    if is_protected_branch(&branch, &git_ops.config.protected_branches) && !args.allow_protected {
+        if git_ops.dry_run {
+            warn!("[DRY RUN] Branch {} is protected; a real run would abort without --allow-protected", branch);
+        } else {
+            error!("Refusing to commit to protected branch {}", branch);
+            return Err(anyhow!(
+                "Branch {} is protected; switch to a feature branch or pass --allow-protected",
+                branch
+            ));
+        }
+    }
+
+    let ticket = branch_ticket(&git_ops.config, &branch)?;
+
+    let files = args.files.clone().unwrap_or_else(|| vec![String::from(".")]);
+    let update_only = if args.update_only {
+        true
+    } else if args.include_untracked {
+        false
+    } else {
+        git_ops.config.default_add_mode == "update"
+    };
+
+    let (pull, reason) = pull_decision(git_ops.config.auto_pull, args.pull, args.no_pull);
+    info!("{} pull before commit ({})", if pull { "Running" } else { "Skipping" }, reason);
+    if pull {
+        git_ops.pull(args.abort_on_conflict)?;
+    }
+
+    if let Some(command) = &git_ops.config.pre_commit_command {
+        if args.skip_checks {
+            warn!("Skipping pre-commit command: {}", command);
+        } else {
+            git_ops.run_pre_commit_command(command)?;
+        }
+    }
+
+    let files = if args.files.is_some() {
+        // git add refuses the whole batch over one bad pathspec, so skip those instead.
+        let unmatched = git_ops.unmatched_pathspecs(&files);
+        for pathspec in &unmatched {
+            warn!("Pathspec {:?} did not match any files; skipping it", pathspec);
+        }
+        let matched: Vec<String> = files.into_iter().filter(|file| !unmatched.contains(file)).collect();
+        if matched.is_empty() {
+            return Err(anyhow!("None of the given files match anything: {}", unmatched.join(", ")));
+        }
+        matched
+    } else {
+        files
+    };
+
+    if !args.patch && !args.allow_excluded && !git_ops.config.commit_exclude.is_empty() {
+        check_commit_exclude(git_ops, &files, update_only)?;
+    }
+
+    if args.patch {
+        git_ops.add_patch(&files)?;
+    } else {
+        if args.files.is_none() && !confirm_staging_all(git_ops, yes, update_only)? {
+            warn!("Commit cancelled");
+            return Ok(());
+        }
+        git_ops.add_files(&files, update_only)?;
+    }
+
+    if args.preview && !preview_staged(git_ops, yes, args.no_pager)? {
+        warn!("Commit cancelled; staged changes were left in place");
+        return Ok(());
+    }
+
+    let use_editor = args.message.is_none()
+        && args.message_file.is_none()
+        && !args.amend
+        && (args.edit || git_ops.config.interactive_message);
+    if git_ops.dry_run {
+        match (&args.message, &args.message_file) {
+            (Some(_), _) => info!("[DRY RUN] Using commit message from --message"),
+            (None, Some(path)) => info!("[DRY RUN] Using commit message from {}", path.display()),
+            (None, None) if use_editor => info!("[DRY RUN] Would write the commit message in the editor"),
+            (None, None) => info!("[DRY RUN] Using a generated commit message"),
+        }
+    }
+    let decorate = |message: &str| decorate_commit_message(&git_ops.config, message, ticket.as_deref(), &co_authors);
+    let message = message.map(|message| decorate(&message));
+
+    if args.amend {
+        if message.is_none() && !args.co_authors.is_empty() {
+            return Err(anyhow!("--co-author requires a new message (-m or -F) when amending"));
+        }
+        if let (Some(message), false) = (&message, args.no_lint) {
+            lint_commit_message(message, &git_ops.config)?;
+        }
+        git_ops.commit_amend(message.as_deref(), &commit_options)?;
+
+        let current_branch = git_ops.get_current_branch()?;
+        if force_mode == ForceMode::None {
+            warn!("Amended commits rewrite history; a force push may be required");
+        }
+        git_ops
+            .push_with_upstream(&current_branch, upstream, force_mode, args.no_verify_push)
+            .map_err(|e| e.context("The last commit was amended, so the remote may need a force push (--force)"))?;
+
+        info!("Successfully amended and pushed changes");
+        return Ok(());
+    }
+
+    let has_changes = if !git_ops.dry_run {
+        // Only what is staged will be committed; other changes in the tree don't count.
+        git_ops.has_staged_changes()?
+    } else if update_only || args.patch {
+        git_ops.has_tracked_changes()?
+    } else {
+        git_ops.has_changes()?
+    };
+    if !has_changes {
+        match when_empty {
+            EmptyCommit::Create => {
+                info!("No changes to commit; creating an empty commit");
+                commit_options.allow_empty = true;
+            }
+            EmptyCommit::Fail => {
+                error!("No changes to commit");
+                return Err(GitError::NothingToCommit.into());
+            }
+            EmptyCommit::Skip => {
+                warn!("No changes to commit");
+                return Ok(());
+            }
+        }
+    }
+
+    if use_editor {
+        if conventional.is_some() {
+            warn!("Conventional commit options are not applied to a message written in the editor");
+        }
+        let mut trailers = Vec::new();
+        match &ticket {
+            Some(ticket) if git_ops.config.ticket_placement == "trailer" => trailers.push(format!("Refs: {}", ticket)),
+            Some(ticket) => warn!("Add ticket {} to the message yourself; only trailer placement is automatic", ticket),
+            None => {}
+        }
+        trailers.extend(co_authors.iter().map(|co_author| format!("Co-authored-by: {}", co_author.trim())));
+        git_ops.commit_in_editor(&trailers, &commit_options)?;
+    } else {
+        let commit_msg = message.unwrap_or_else(|| {
+            let subject = git_ops
+                .config
+                .message_from_diff
+                .then(|| git_ops.staged_diff_stat().ok())
+                .flatten()
+                .and_then(|stat| describe_diff_stat(&stat));
+            decorate(&generate_commit_message(
+                &git_ops.config.commit_template,
+                subject.as_deref(),
+                conventional.as_ref(),
+            ))
+        });
+        if !args.no_lint {
+            lint_commit_message(&commit_msg, &git_ops.config)?;
+        }
+        git_ops.commit(&commit_msg, &commit_options)?;
+    }
+
+    let current_branch = git_ops.get_current_branch()?;
+    git_ops.push_with_upstream(&current_branch, upstream, force_mode, args.no_verify_push)?;
+
+    info!("Successfully committed and pushed changes");
+    Ok(())
+}
+
+fn run_pr(git_ops: &GitOps, title: Option<&str>, base: Option<&str>, draft: bool) -> Result<()> {
+    match Command::new("gh").arg("--version").output() {
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(anyhow!("The GitHub CLI (gh) is not installed; see https://cli.github.com"));
+        }
+        Err(e) => return Err(anyhow!("Failed to run gh: {}", e)),
+    }
+    let authenticated = Command::new("gh")
+        .args(["auth", "status"])
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+    if !authenticated {
+        return Err(anyhow!("The GitHub CLI is not authenticated; run `gh auth login` first"));
+    }
+
+    let branch = git_ops.get_current_branch()?;
+    let upstream = git_ops.upstream_status()?;
+    if upstream.upstream.is_none() || upstream.ahead > 0 {
+        git_ops.push(&branch, ForceMode::None, false)?;
+    }
+
+    let (subject, body) = git_ops.last_commit_message()?;
+    let mut args = vec![
+        String::from("pr"),
+        String::from("create"),
+        String::from("--head"),
+        branch,
+        String::from("--title"),
+        title.map(String::from).unwrap_or(subject),
+        String::from("--body"),
+        body,
+    ];
+    if let Some(base) = base {
+        args.extend([String::from("--base"), base.to_string()]);
+    }
+    if draft {
+        args.push(String::from("--draft"));
+    }
+
+    if git_ops.dry_run {
+        info!("[DRY RUN] Would run: gh {}", args.join(" "));
+        git_ops.record(PlanStep::PullRequest { args });
+        return Ok(());
+    }
+
+    let status = Command::new("gh")
+        .args(&args)
+        .status()
+        .map_err(|e| anyhow!("Failed to run gh pr create: {}", e))?;
+    if !status.success() {
+        error!("gh pr create failed");
+        return Err(anyhow!("gh pr create failed (exit status {})", status.code().unwrap_or(-1)));
+    }
+
+    Ok(())
+}
+
+/// Splits merged branches into the ones `prune` may delete and the skipped ones
+/// (with the reason): the current branch, the target and protected branches stay.
+fn classify_merged_branches(
+    merged: Vec<String>,
+    current: &str,
+    target: &str,
+    protected_branches: &[String],
+) -> (Vec<String>, Vec<String>) {
+    let mut deletable = Vec::new();
+    let mut skipped = Vec::new();
+    for branch in merged {
+        if branch == current || branch == target {
+            skipped.push(format!("{} (current or target branch)", branch));
+        } else if is_protected_branch(&branch, protected_branches) {
+            skipped.push(format!("{} (protected)", branch));
+        } else {
+            deletable.push(branch);
+        }
+    }
+    (deletable, skipped)
+}
+
+fn run_prune(git_ops: &GitOps, list_only: bool, merged_into: Option<&str>, remote_prune: bool) -> Result<()> {
+    let current = git_ops.get_current_branch()?;
+    let target = merged_into.unwrap_or(&current);
+    git_ops.ensure_commit(target)?;
+
+    let (deletable, skipped) = classify_merged_branches(
+        git_ops.merged_branches(target)?,
+        &current,
+        target,
+        &git_ops.config.protected_branches,
+    );
+
+    if list_only {
+        if deletable.is_empty() && !quiet() {
+            println!("No merged branches to prune");
+        }
+        for branch in &deletable {
+            println!("{}", branch);
+        }
+    } else {
+        for branch in &deletable {
+            git_ops.delete_branch(branch, false)?;
+        }
+        if !quiet() && git_ops.plan.is_none() {
+            let verb = if git_ops.dry_run { "Would delete" } else { "Deleted" };
+            println!("{} {} branch(es) merged into {}", verb, deletable.len(), target);
+            for branch in &deletable {
+                println!("  {}", branch);
+            }
+            if !skipped.is_empty() {
+                println!("Skipped {}:", skipped.len());
+                for branch in &skipped {
+                    println!("  {}", branch);
+                }
+            }
+        }
+    }
+
+    if remote_prune {
+        let pruned = git_ops.remote_prune(list_only)?;
+        if !quiet() && git_ops.plan.is_none() {
+            let verb = if git_ops.dry_run || list_only { "Would prune" } else { "Pruned" };
+            println!("{} {} stale remote-tracking ref(s) from {}", verb, pruned.len(), git_ops.remote());
+            for reference in &pruned {
+                println!("  {}", reference);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_clean(git_ops: &GitOps, directories: bool, force: bool, ignored: bool, yes: bool) -> Result<()> {
+    let paths = git_ops.clean(directories, ignored, false)?;
+    if paths.is_empty() {
+        if !quiet() {
+            println!("Nothing to clean");
+        }
+        return Ok(());
+    }
+
+    if git_ops.plan.is_none() {
+        println!("{}:", if force { "Files to be removed" } else { "Would remove" });
+        for path in &paths {
+            println!("  {}", path);
+        }
+    }
+
+    if !force {
+        if !quiet() && git_ops.plan.is_none() {
+            println!("Rerun with --force to delete them");
+        }
+        return Ok(());
+    }
+    if git_ops.dry_run {
+        git_ops.record(PlanStep::Clean { paths: paths.clone() });
+        info!("[DRY RUN] Would remove {} untracked path(s)", paths.len());
+        return Ok(());
+    }
+
+    if !yes && !confirm(&format!("Permanently delete {} path(s)?", paths.len()))? {
+        warn!("Clean cancelled");
+        return Ok(());
+    }
+
+    let removed = git_ops.clean(directories, ignored, true)?;
+    info!("Removed {} untracked path(s)", removed.len());
+    Ok(())
+}
+
+/// Returns what was done, or `None` for listings and operations the user backed out of.
+fn run_branch_command(
+    git_ops: &GitOps,
+    cmd: &BranchCommands,
+    yes: bool,
+    format: OutputFormat,
+) -> Result<Option<BranchOpResult>> {
+    let done = |branch: String, from: Option<String>| {
+        Some(BranchOpResult { action: cmd.action(), branch: Some(branch), from, ok: true, error: None })
+    };
+
+    match cmd {
+        BranchCommands::Create { name, no_prefix, from, branch_type, raw } => {
+            let name = match branch_type {
+                Some(branch_type) => {
+                    validate_commit_type(branch_type, &git_ops.config.extra_commit_types)?;
+                    let name = if *raw { name.clone() } else { slugify(name) };
+                    if name.is_empty() {
+                        return Err(anyhow!("Branch name must contain at least one letter or digit"));
+                    }
+                    name
+                }
+                None => name.clone(),
+            };
+            let branch = git_ops.create_branch(&name, branch_type.as_deref(), !*no_prefix, from.as_deref())?;
+            Ok(done(branch, from.clone()))
+        }
+        BranchCommands::Switch { name: Some(name), stash } => {
+            git_ops.switch_branch(name, *stash)?;
+            Ok(done(name.clone(), None))
+        }
+        BranchCommands::Switch { name: None, stash } => {
+            let branches = git_ops.list_branches(false)?;
+            let current_branch = git_ops.get_current_branch().ok();
+            match select_branch(&branches, current_branch.as_deref())? {
+                Some(name) => {
+                    git_ops.switch_branch(&name, *stash)?;
+                    Ok(done(name, current_branch))
+                }
+                None => {
+                    warn!("No branch selected; not switching");
+                    Ok(None)
+                }
+            }
+        }
+        BranchCommands::Delete { name, force } => {
+            let confirmed = !*force
+                || yes
+                || git_ops.dry_run
+                || confirm(&format!("Force deleting {} may lose unmerged commits. Continue?", name))?;
+            if !confirmed {
+                warn!("Branch deletion cancelled");
+                return Ok(None);
+            }
+            git_ops.delete_branch(name, *force)?;
+            Ok(done(name.clone(), None))
+        }
+        BranchCommands::Rename { old, new, push } => {
+            let old = match old {
+                Some(old) => old.clone(),
+                None => git_ops.get_current_branch()?,
+            };
+            git_ops.rename_branch(Some(&old), new, *push)?;
+            Ok(done(new.clone(), Some(old)))
+        }
+        BranchCommands::List { all } => {
+            let branches = git_ops.branch_infos(*all)?;
+            if format == OutputFormat::Json {
+                println!("{}", serde_json::to_string(&branches)?);
+                return Ok(None);
+            }
+
+            let width = branches.iter().map(|branch| branch.name.len()).max().unwrap_or(0);
+            for branch in &branches {
+                let name = format!("{:<width$}", branch.name, width = width);
+                let tracking = branch.to_string();
+                let tracking = if branch.gone || branch.tracking.behind > 0 {
+                    paint(&tracking, Color::Yellow)
+                } else {
+                    tracking
+                };
+                let line = if branch.current {
+                    format!("* {}  {}", paint(&name, Color::Green), tracking)
+                } else {
+                    format!("  {}  {}", name, tracking)
+                };
+                println!("{}", line.trim_end());
+            }
+            Ok(None)
+        }
+    }
+}
+
+fn run_restore(git_ops: &GitOps, files: &[String], staged: bool, yes: bool) -> Result<()> {
+    let untracked: Vec<&str> = files
+        .iter()
+        .map(String::as_str)
+        .filter(|file| !git_ops.is_tracked(file))
+        .collect();
+    if !untracked.is_empty() {
+        return Err(anyhow!("Not tracked by git, nothing to restore: {}", untracked.join(", ")));
+    }
+
+    let (changed, unchanged): (Vec<String>, Vec<String>) =
+        files.iter().cloned().partition(|file| git_ops.has_changes_to_restore(file, staged));
+    if !quiet() {
+        for file in &unchanged {
+            println!("{} has no {} to restore", file, if staged { "staged changes" } else { "changes" });
+        }
+    }
+    if changed.is_empty() {
+        return Ok(());
+    }
+
+    if !staged && !yes && !git_ops.dry_run {
+        println!("Changes to be discarded:");
+        for file in &changed {
+            println!("  {}", file);
+        }
+        if !confirm(&format!("Discard changes to {} path(s)? This cannot be undone.", changed.len()))? {
+            warn!("Restore cancelled");
+            return Ok(());
+        }
+    }
+
+    git_ops.restore(&changed, staged)
+}
+
+fn run_changelog(git_ops: &GitOps, since: Option<&str>, output: Option<&Path>) -> Result<()> {
+    let since = match since {
+        Some(since) => {
+            if !git_ops.git_succeeds(&["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", since)]) {
+                return Err(anyhow!("Cannot build changelog: {} does not exist", since));
+            }
+            Some(since.to_string())
+        }
+        None => git_ops.last_tag(),
+    };
+
+    let commits = git_ops.commits_since(since.as_deref())?;
+    let title = match &since {
+        Some(since) => format!("Changes since {}", since),
+        None => String::from("Changes"),
+    };
+    let changelog = render_changelog(&title, &commits);
+
+    match output {
+        Some(path) if git_ops.dry_run => {
+            info!("[DRY RUN] Would write changelog to {}", path.display());
+            print!("{}", changelog);
+        }
+        Some(path) => {
+            fs::write(path, &changelog)
+                .map_err(|e| anyhow!("Failed to write changelog to {}: {}", path.display(), e))?;
+            info!("Wrote changelog with {} commit(s) to {}", commits.len(), path.display());
+        }
+        None => print!("{}", changelog),
+    }
+    Ok(())
+}
+
+fn run_release(git_ops: &GitOps, version: &str, no_push: bool) -> Result<()> {
+    let tag = format!("v{}", version.strip_prefix('v').unwrap_or(version));
+    if git_ops.tag_exists(&tag) {
+        return Err(anyhow!("Tag {} already exists; pick a new version", tag));
+    }
+
+    let branch = git_ops.get_current_branch()?;
+    let default_branch = git_ops.default_branch()?;
+    if branch != default_branch {
+        error!("Releases must be cut from {}", default_branch);
+        return Err(anyhow!("Currently on {}; switch to {} before releasing", branch, default_branch));
+    }
+    if git_ops.has_tracked_changes()? {
+        return Err(anyhow!("Working tree has uncommitted changes; commit or stash them before releasing"));
+    }
+
+    let last_tag = git_ops.last_tag();
+    let commits = git_ops.commits_since(last_tag.as_deref())?;
+    let changelog = render_changelog(&tag, &commits);
+
+    if git_ops.dry_run && git_ops.plan.is_none() {
+        println!("[DRY RUN] Release plan for {}:", tag);
+        match &last_tag {
+            Some(last_tag) => println!("  1. collect {} commit(s) since {}", commits.len(), last_tag),
+            None => println!("  1. collect all {} commit(s) (no previous tag)", commits.len()),
+        }
+        println!("  2. create annotated tag {} with the changelog below", tag);
+        if no_push {
+            println!("  3. skip pushing (--no-push)");
+        } else {
+            println!("  3. push {} and tag {} to {}", branch, tag, git_ops.remote());
+        }
+        println!();
+        print!("{}", changelog);
+    }
+
+    git_ops.create_tag(&tag, Some(changelog.trim_end()), git_ops.sign_tags())?;
+    if no_push {
+        info!("Created tag {}; skipping push (--no-push)", tag);
+        return Ok(());
+    }
+
+    git_ops.push(&branch, ForceMode::None, false)?;
+    git_ops.push_tag(&tag)?;
+
+    info!("Released {}", tag);
+    Ok(())
+}
+
+fn run_squash(git_ops: &GitOps, onto: &str, message: Option<&str>) -> Result<()> {
+    if !git_ops.git_succeeds(&["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", onto)]) {
+        return Err(anyhow!("Cannot squash: {} does not exist", onto));
+    }
+
+    let branch = git_ops.get_current_branch()?;
+    if is_protected_branch(&branch, &git_ops.config.protected_branches) {
+        error!("Refusing to squash protected branch {}", branch);
+        return Err(anyhow!("Branch {} is protected; squashing would rewrite its history", branch));
+    }
+    if git_ops.has_tracked_changes()? {
+        return Err(anyhow!("Working tree has uncommitted changes; commit or stash them before squashing"));
+    }
+
+    let merge_base = git_ops.run_git(&["merge-base", onto, "HEAD"], "Merge-base lookup")?;
+    let count = git_ops.run_git(&["rev-list", "--count", &format!("{}..HEAD", merge_base)], "Commit count")?;
+    if count == "0" {
+        warn!("{} has no commits since {}; nothing to squash", branch, onto);
+        return Ok(());
+    }
+
+    if git_ops.dry_run {
+        info!("[DRY RUN] Merge base of {} and {} is {}; would squash {} commit(s)", branch, onto, merge_base, count);
+    }
+
+    let message = message
+        .map(String::from)
+        .unwrap_or_else(|| generate_commit_message(&git_ops.config.commit_template, None, None));
+    let options = CommitOptions {
+        no_verify: false,
+        sign: git_ops.config.sign_commits,
+        allow_empty: false,
+        author: None,
+        sign_off: git_ops.sign_off_identity(false)?,
+    };
+
+    git_ops.reset(&merge_base, ResetMode::Soft)?;
+    git_ops.commit(&message, &options)?;
+
+    info!("Squashed {} commit(s) on {} into one", count, branch);
+    Ok(())
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+fn install_interrupt_handler() {
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+    unsafe {
+        libc::signal(libc::SIGINT, on_interrupt as *const () as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn install_interrupt_handler() {}
+
+/// Sleeps in short steps so Ctrl-C is noticed promptly; returns false once interrupted.
+fn sleep_unless_interrupted(duration: Duration) -> bool {
+    let deadline = Instant::now() + duration;
+    while Instant::now() < deadline {
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(100).min(deadline - Instant::now()));
+    }
+    !INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Debounce bookkeeping for `watch`, kept free of I/O so the timing can be tested.
+#[derive(Debug, Default)]
+struct WatchDebounce {
+    /// The last snapshot seen and when it was first seen.
+    pending: Option<(u64, Instant)>,
+    /// The snapshot a commit was last attempted for, so a failing or dry-run
+    /// commit isn't retried until something changes again.
+    attempted: Option<u64>,
+}
+
+impl WatchDebounce {
+    /// Records the snapshot taken at `now`; true once it has held still for `debounce`.
+    fn observe(&mut self, snapshot: Option<u64>, now: Instant, debounce: Duration) -> bool {
+        let snapshot = match snapshot {
+            Some(snapshot) if self.attempted != Some(snapshot) => snapshot,
+            _ => {
+                self.pending = None;
+                return false;
+            }
+        };
+        match self.pending {
+            Some((previous, since)) if previous == snapshot => now.duration_since(since) >= debounce,
+            _ => {
+                self.pending = Some((snapshot, now));
+                false
+            }
+        }
+    }
+
+    fn attempt(&mut self) {
+        self.attempted = self.pending.take().map(|(snapshot, _)| snapshot);
+    }
+
+    fn committed(&mut self) {
+        self.attempted = None;
+    }
+}
+
+fn run_watch(git_ops: &GitOps, interval: u64, debounce: u64) -> Result<()> {
+    if interval == 0 {
+        return Err(anyhow!("--interval must be at least 1 second"));
+    }
+    let branch = git_ops.get_current_branch()?;
+    if is_protected_branch(&branch, &git_ops.config.protected_branches) {
+        return Err(anyhow!("Branch {} is protected; switch to another branch before watching", branch));
+    }
+
+    install_interrupt_handler();
+    if !quiet() {
+        println!("Watching for changes on {} every {}s (Ctrl-C to stop)", branch, interval);
+    }
+
+    let mut debounce_state = WatchDebounce::default();
+    let debounce = Duration::from_secs(debounce);
+    let args = CommitArgs::default();
+
+    while sleep_unless_interrupted(Duration::from_secs(interval)) {
+        let snapshot = git_ops.watch_snapshot()?;
+        if !debounce_state.observe(snapshot, Instant::now(), debounce) {
+            continue;
+        }
+
+        if let Some(operation) = git_ops.operation_in_progress() {
+            warn!("A {} is in progress; not committing until it is finished", operation);
+            continue;
+        }
+
+        debounce_state.attempt();
+        match run_commit(git_ops, &args, true) {
+            Ok(()) if git_ops.dry_run => {}
+            Ok(()) => {
+                // Whatever the commit left behind is the new baseline.
+                debounce_state.committed();
+                if !quiet() {
+                    println!("{} Committed and pushed changes", chrono::Local::now().format("%H:%M:%S"));
+                }
+            }
+            Err(e) => eprintln!("Auto-commit failed: {:#}", e),
+        }
+    }
+
+    if !quiet() {
+        println!("Stopped watching");
+    }
+    Ok(())
+}
+
+fn run_wip(git_ops: &GitOps, push: bool) -> Result<()> {
+    let branch = git_ops.get_current_branch()?;
+    if is_protected_branch(&branch, &git_ops.config.protected_branches) {
+        warn!("Creating a WIP commit on protected branch {}", branch);
+    }
+    if git_ops.config.auto_pull {
+        warn!("Skipping auto_pull for WIP commit");
+    }
+
+    git_ops.add_files(&[String::from(".")], false)?;
+    if !git_ops.has_changes()? {
+        warn!("No changes to commit");
+        return Ok(());
+    }
+
+    let message = format!("{}: {}", git_ops.config.wip_prefix, chrono::Local::now().format("%Y-%m-%d %H:%M:%S"));
+    let options = CommitOptions {
+        no_verify: true,
+        sign: git_ops.config.sign_commits,
+        allow_empty: false,
+        author: None,
+        sign_off: git_ops.sign_off_identity(false)?,
+    };
+    git_ops.commit(&message, &options)?;
+
+    if push {
+        git_ops.push(&branch, ForceMode::None, true)?;
+    }
+
+    info!("Created WIP commit on {}", branch);
+    Ok(())
+}
+
+fn run_sync(git_ops: &GitOps, abort_on_conflict: bool) -> Result<()> {
+    if git_ops.has_changes()? {
+        return Err(anyhow!("Working tree has uncommitted changes; commit or stash them before syncing"));
+    }
+
+    let branch = git_ops.get_current_branch()?;
+    let has_upstream = git_ops.has_upstream(&branch);
+
+    if git_ops.dry_run && git_ops.plan.is_none() {
+        println!("[DRY RUN] Sync plan for {}:", branch);
+        if has_upstream {
+            println!("  1. pull from upstream using {} strategy", git_ops.config.pull_strategy);
+            println!("  2. push {} to {}", branch, git_ops.remote());
+        } else {
+            println!("  1. skip pull (no upstream configured)");
+            println!("  2. push {} to {} with --set-upstream", branch, git_ops.remote());
+        }
+        return Ok(());
+    }
+
+    if has_upstream {
+        info!("Pulling upstream changes into {} using {} strategy", branch, git_ops.config.pull_strategy);
+        git_ops.pull(abort_on_conflict)?;
+    } else {
+        info!("No upstream configured for {}; skipping pull", branch);
+    }
+
+    info!("Pushing {} to {}", branch, git_ops.remote());
+    git_ops.push(&branch, ForceMode::None, false)?;
+
+    info!("Successfully synced {}", branch);
+    Ok(())
+}
+
+static COLOR_ENABLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy)]
+enum Color {
+    Red,
+    Green,
+    Yellow,
+}
+
+fn init_color(no_color: bool) -> bool {
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let enabled = !no_color && !no_color_env && io::stdout().is_terminal();
+    COLOR_ENABLED.store(enabled, Ordering::Relaxed);
+    enabled
+}
+
+fn color_enabled() -> bool {
+    COLOR_ENABLED.load(Ordering::Relaxed)
+}
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+fn paint(text: &str, color: Color) -> String {
+    if !color_enabled() {
+        return text.to_string();
+    }
+
+    let code = match color {
+        Color::Red => 31,
+        Color::Green => 32,
+        Color::Yellow => 33,
+    };
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
+
+fn terminal_height() -> usize {
+    std::env::var("LINES")
+        .ok()
+        .and_then(|lines| lines.parse().ok())
+        .unwrap_or(24)
+}
+
+fn print_paged(text: &str, no_pager: bool) -> Result<()> {
+    if no_pager || !io::stdout().is_terminal() || text.lines().count() < terminal_height() {
+        println!("{}", text);
+        return Ok(());
+    }
+
+    let pager = std::env::var("GIT_PAGER")
+        .or_else(|_| std::env::var("PAGER"))
+        .unwrap_or_else(|_| String::from("less -R"));
+    let mut parts = pager.split_whitespace();
+    let Some(program) = parts.next() else {
+        println!("{}", text);
+        return Ok(());
+    };
+
+    let mut child = match Command::new(program).args(parts).stdin(Stdio::piped()).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("Failed to start pager {}: {}", pager, e);
+            println!("{}", text);
+            return Ok(());
+        }
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager may exit before reading everything (e.g. the user quits early).
+        let _ = writeln!(stdin, "{}", text);
+    }
+    child.wait().map_err(|e| anyhow!("Pager failed: {}", e))?;
+
+    Ok(())
+}
+
+fn print_status_entries(entries: &[StatusEntry]) {
+    let staged: Vec<_> = entries
+        .iter()
+        .filter_map(|e| e.staged.filter(|s| *s != FileState::Unmerged).map(|s| (s, e)))
+        .collect();
+    let conflicted: Vec<_> = entries.iter().filter(|e| e.staged == Some(FileState::Unmerged)).collect();
+    let modified: Vec<_> = entries
+        .iter()
+        .filter_map(|e| {
+            e.unstaged
+                .filter(|s| matches!(s, FileState::Modified | FileState::TypeChanged))
+                .map(|s| (s, e))
+        })
+        .collect();
+    let deleted: Vec<_> = entries.iter().filter(|e| e.unstaged == Some(FileState::Deleted)).collect();
+    let untracked: Vec<_> = entries.iter().filter(|e| e.unstaged == Some(FileState::Untracked)).collect();
+
+    if !staged.is_empty() {
+        println!("\nStaged:");
+        for (state, entry) in &staged {
+            let color = if *state == FileState::Deleted { Color::Red } else { Color::Green };
+            println!("  {} {}", paint(&format!("{:<10}", state.label()), color), entry.display_path());
+        }
+    }
+    if !conflicted.is_empty() {
+        println!("\nConflicted:");
+        for entry in &conflicted {
+            println!("  {}", paint(&entry.path, Color::Red));
+        }
+    }
+    if !modified.is_empty() {
+        println!("\nModified:");
+        for (state, entry) in &modified {
+            println!("  {} {}", paint(&format!("{:<10}", state.label()), Color::Yellow), entry.path);
+        }
+    }
+    if !deleted.is_empty() {
+        println!("\nDeleted:");
+        for entry in &deleted {
+            println!("  {}", paint(&entry.path, Color::Red));
+        }
+    }
+    if !untracked.is_empty() {
+        println!("\nUntracked:");
+        for entry in &untracked {
+            println!("  {}", paint(&entry.path, Color::Yellow));
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// A (sub)command path such as `branch create` with the words that may follow it.
+struct CompletionNode {
+    path: Vec<String>,
+    subcommands: Vec<(String, String)>,
+    flags: Vec<(String, String)>,
+}
+
+fn completion_nodes(command: &clap::Command, path: Vec<String>, nodes: &mut Vec<CompletionNode>) {
+    let about = |help: Option<&clap::builder::StyledStr>| help.map(|h| h.to_string()).unwrap_or_default();
+
+    let mut flags = Vec::new();
+    for arg in command.get_arguments().filter(|arg| !arg.is_hide_set()) {
+        let help = about(arg.get_help());
+        if arg.is_positional() {
+            flags.extend(arg.get_possible_values().iter().map(|value| (value.get_name().to_string(), help.clone())));
+            continue;
+        }
+        if let Some(long) = arg.get_long() {
+            flags.push((format!("--{}", long), help.clone()));
+        }
+        if let Some(short) = arg.get_short() {
+            flags.push((format!("-{}", short), help));
+        }
+    }
 
-        /// Files to add (default: all)
-        #[arg(short, long)]
-        files: Option<Vec<String>>,
+    let subcommands = command
+        .get_subcommands()
+        .map(|sub| (sub.get_name().to_string(), about(sub.get_about())))
+        .collect();
+    nodes.push(CompletionNode { path: path.clone(), subcommands, flags });
 
-        /// Use conventional commit format
-        #[arg(short, long)]
-        conventional: bool,
-    },
-    /// Branch operations
-    Branch {
-        #[command(subcommand)]
-        cmd: BranchCommands,
-    },
-    /// Initialize configuration
-    Init,
-    /// Show status
-    Status,
+    for sub in command.get_subcommands().filter(|sub| sub.get_name() != "help") {
+        let mut sub_path = path.clone();
+        sub_path.push(sub.get_name().to_string());
+        completion_nodes(sub, sub_path, nodes);
+    }
 }
 
-#[derive(Subcommand)]
-enum BranchCommands {
-    /// Create a new branch
-    Create { name: String },
-    /// Switch to a branch
-    Switch { name: String },
-    /// Delete a branch
-    Delete { name: String },
+fn generate_completions(shell: Shell) -> String {
+    let mut command = Cli::command();
+    command.build();
+    let bin = command.get_name().to_string();
+    let mut nodes = Vec::new();
+    completion_nodes(&command, Vec::new(), &mut nodes);
+
+    match shell {
+        Shell::Bash => bash_completions(&bin, &nodes),
+        Shell::Zsh => format!(
+            "#compdef {}\n\nautoload -U +X bashcompinit && bashcompinit\n\n{}",
+            bin,
+            bash_completions(&bin, &nodes)
+        ),
+        Shell::Fish => fish_completions(&bin, &nodes),
+        Shell::Powershell => powershell_completions(&bin, &nodes),
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-struct Config {
-    default_remote: String,
-    commit_template: String,
-    auto_pull: bool,
+fn completion_words(node: &CompletionNode) -> Vec<&str> {
+    node.subcommands
+        .iter()
+        .chain(&node.flags)
+        .map(|(word, _)| word.as_str())
+        .collect()
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            default_remote: String::from("origin"),
-            commit_template: String::from("feat: {}"),
-            auto_pull: true,
+fn bash_completions(bin: &str, nodes: &[CompletionNode]) -> String {
+    let function = format!("_{}", bin.replace('-', "_"));
+    let paths: Vec<String> = nodes.iter().skip(1).map(|node| format!("\"{}\"", node.path.join(" "))).collect();
+    let cases: String = nodes
+        .iter()
+        .map(|node| format!("        \"{}\") opts=\"{}\" ;;\n", node.path.join(" "), completion_words(node).join(" ")))
+        .collect();
+
+    format!(
+        r#"{function}() {{
+    local cur word path="" next opts=""
+    cur="${{COMP_WORDS[COMP_CWORD]}}"
+    for word in "${{COMP_WORDS[@]:1:COMP_CWORD-1}}"; do
+        next="${{path:+$path }}$word"
+        case "$next" in
+            {paths}) path="$next" ;;
+        esac
+    done
+    case "$path" in
+{cases}    esac
+    COMPREPLY=($(compgen -W "$opts" -- "$cur"))
+}}
+complete -F {function} {bin}
+"#,
+        function = function,
+        paths = paths.join("|"),
+        cases = cases,
+        bin = bin,
+    )
+}
+
+fn fish_completions(bin: &str, nodes: &[CompletionNode]) -> String {
+    let quote = |text: &str| format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"));
+    let mut script = String::new();
+
+    for node in nodes {
+        let condition = if node.path.is_empty() {
+            String::from("__fish_use_subcommand")
+        } else {
+            let mut conditions: Vec<String> =
+                node.path.iter().map(|name| format!("__fish_seen_subcommand_from {}", name)).collect();
+            let children: Vec<&str> = node.subcommands.iter().map(|(name, _)| name.as_str()).collect();
+            if !children.is_empty() {
+                conditions.push(format!("not __fish_seen_subcommand_from {}", children.join(" ")));
+            }
+            conditions.join("; and ")
+        };
+
+        for (name, about) in &node.subcommands {
+            script.push_str(&format!(
+                "complete -c {} -f -n {} -a {} -d {}\n",
+                bin,
+                quote(&condition),
+                name,
+                quote(about)
+            ));
+        }
+        for (flag, help) in &node.flags {
+            let option = match (flag.strip_prefix("--"), flag.strip_prefix('-')) {
+                (Some(long), _) => format!("-l {}", long),
+                (None, Some(short)) => format!("-s {}", short),
+                (None, None) => format!("-a {}", flag),
+            };
+            script.push_str(&format!(
+                "complete -c {} -f -n {} {} -d {}\n",
+                bin,
+                quote(&condition),
+                option,
+                quote(help)
+            ));
         }
     }
+
+    script
 }
 
-struct GitOps {
-    config: Config,
-    dry_run: bool,
+fn powershell_completions(bin: &str, nodes: &[CompletionNode]) -> String {
+    let table: String = nodes
+        .iter()
+        .map(|node| {
+            let words: Vec<String> = completion_words(node).iter().map(|word| format!("'{}'", word)).collect();
+            format!("        '{}' = @({})\n", node.path.join(" "), words.join(", "))
+        })
+        .collect();
+
+    format!(
+        r#"Register-ArgumentCompleter -Native -CommandName '{bin}' -ScriptBlock {{
+    param($wordToComplete, $commandAst, $cursorPosition)
+    $completions = @{{
+{table}    }}
+    $path = ''
+    foreach ($element in $commandAst.CommandElements | Select-Object -Skip 1) {{
+        $word = $element.ToString()
+        if ($word -eq $wordToComplete) {{ break }}
+        $next = if ($path) {{ "$path $word" }} else {{ $word }}
+        if ($completions.ContainsKey($next)) {{ $path = $next }}
+    }}
+    $completions[$path] | Where-Object {{ $_ -like "$wordToComplete*" }} | ForEach-Object {{
+        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+    }}
+}}
+"#,
+        bin = bin,
+        table = table,
+    )
 }
 
-impl GitOps {
-    fn new(config: Config, dry_run: bool) -> Self {
-        Self { config, dry_run }
-    }
+/// Runs every check even when earlier ones fail, so it loads the config itself
+/// instead of letting `run` bail out on an invalid file.
+fn run_doctor(cli: &Cli, fail_on: CheckStatus) -> Result<()> {
+    let mut checks = Vec::new();
 
-    fn get_current_branch(&self) -> Result<String> {
-        let output = Command::new("git")
-            .args(["rev-parse", "--abbrev-ref", "HEAD"])
-            .output()
-            .map_err(|e| anyhow!("Failed to get current branch: {}", e))?;
+    // The config decides which git to probe, so load it first but report it second.
+    let (mut config, config_check) = match load_config(cli.config.as_deref(), false, !cli.no_global) {
+        Ok(config) => {
+            let check = match cli.config.clone().or_else(find_config_file) {
+                Some(path) => DoctorCheck::pass("config", format!("{} is valid", path.display())),
+                None => DoctorCheck::warn(
+                    "config",
+                    "no git-automate.toml found; using built-in defaults",
+                    "run `git-automate init` to write one",
+                ),
+            };
+            (config, check)
+        }
+        Err(e) => (
+            Config::default(),
+            DoctorCheck::fail("config", format!("{:#}", e), "fix the listed settings in the config file"),
+        ),
+    };
+    if let Some(timeout) = cli.timeout {
+        config.command_timeout_secs = Some(timeout);
+    }
+    let git_ops = GitOps::new(config, false, cli.remote.clone(), false, true, false);
 
-        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    match Command::new(git_ops.git()).arg("--version").output() {
+        Ok(output) if output.status.success() => {
+            checks.push(DoctorCheck::pass("git", String::from_utf8_lossy(&output.stdout).trim().to_string()));
+            checks.push(config_check);
+        }
+        _ => {
+            checks.push(DoctorCheck::fail(
+                "git",
+                format!("cannot run {}", git_ops.git()),
+                "install git or point git_path at the git executable",
+            ));
+            checks.push(config_check);
+            return print_doctor_report(cli, &checks, fail_on);
+        }
     }
 
-    fn check_git_repo(&self) -> bool {
-        Command::new("git")
-            .args(["rev-parse", "--is-inside-work-tree"])
-            .output()
-            .map(|output| output.status.success())
-            .unwrap_or(false)
+    if !git_ops.check_git_repo() {
+        checks.push(DoctorCheck::fail(
+            "repository",
+            "not inside a git work tree",
+            "run from a repository, or create one with `git init`",
+        ));
+        return print_doctor_report(cli, &checks, fail_on);
     }
+    checks.push(DoctorCheck::pass("repository", "inside a git work tree"));
 
-    fn has_changes(&self) -> Result<bool> {
-        let output = Command::new("git")
-            .args(["status", "--porcelain"])
-            .output()
-            .map_err(|e| anyhow!("Failed to check git status: {}", e))?;
+    let remote = git_ops.remote().to_string();
+    if !git_ops.list_remotes().unwrap_or_default().contains(&remote) {
+        checks.push(DoctorCheck::fail(
+            "remote",
+            format!("remote {} is not configured", remote),
+            format!("add it with `git remote add {} <url>` or change default_remote", remote),
+        ));
+    } else {
+        match git_ops.git_output(&["ls-remote", "--heads", &remote], false) {
+            Ok(output) if output.success => {
+                checks.push(DoctorCheck::pass("remote", format!("{} is reachable", remote)))
+            }
+            Ok(output) => checks.push(DoctorCheck::fail(
+                "remote",
+                format!("{} is not reachable: {}", remote, output.stderr.trim()),
+                "check the remote URL, your network and your credentials",
+            )),
+            Err(e) => checks.push(DoctorCheck::fail(
+                "remote",
+                format!("{} could not be queried: {}", remote, e),
+                "check the remote URL, your network and your credentials",
+            )),
+        }
+    }
 
-        Ok(!output.stdout.is_empty())
+    match git_ops.get_current_branch() {
+        Err(_) => checks.push(DoctorCheck::warn(
+            "upstream",
+            "HEAD is detached",
+            "check out a branch before committing",
+        )),
+        Ok(branch) => match git_ops.upstream_status() {
+            Ok(UpstreamStatus { upstream: None, .. }) | Err(_) => checks.push(DoctorCheck::warn(
+                "upstream",
+                format!("{} has no upstream", branch),
+                format!("push it once with `git push -u {} {}`", remote, branch),
+            )),
+            Ok(status) if status.behind > 0 => checks.push(DoctorCheck::warn(
+                "upstream",
+                format!("{} is {} of {}", branch, status, status.upstream.as_deref().unwrap_or_default()),
+                "pull before committing to avoid a rejected push",
+            )),
+            Ok(status) => checks.push(DoctorCheck::pass(
+                "upstream",
+                format!("{} tracks {} ({})", branch, status.upstream.as_deref().unwrap_or_default(), status),
+            )),
+        },
     }
 
-    fn pull(&self) -> Result<()> {
-        if self.dry_run {
-            info!("[DRY RUN] Would pull changes");
-            return Ok(());
+    if let Some(operation) = git_ops.operation_in_progress() {
+        checks.push(DoctorCheck::fail(
+            "working tree",
+            format!("a {} is in progress", operation),
+            format!("finish it or run `git {} --abort`", operation),
+        ));
+    } else {
+        match git_ops.status_entries() {
+            Ok(entries) if entries.is_empty() => checks.push(DoctorCheck::pass("working tree", "clean")),
+            Ok(entries) => checks.push(DoctorCheck::warn(
+                "working tree",
+                format!("{} uncommitted change(s)", entries.len()),
+                "commit or stash them before switching branches",
+            )),
+            Err(e) => checks.push(DoctorCheck::fail("working tree", format!("{:#}", e), "run `git status` for details")),
         }
+    }
 
-        let output = Command::new("git")
-            .args(["pull"])
-            .output()
-            .map_err(|e| anyhow!("Failed to pull changes: {}", e))?;
+    print_doctor_report(cli, &checks, fail_on)
+}
 
-        if !output.status.success() {
-            let err_msg = String::from_utf8_lossy(&output.stderr);
-            error!("Pull failed: {}", err_msg);
-            return Err(anyhow!("Pull failed: {}", err_msg));
+fn print_doctor_report(cli: &Cli, checks: &[DoctorCheck], fail_on: CheckStatus) -> Result<()> {
+    let failed = checks.iter().filter(|check| check.status >= fail_on).count();
+
+    match cli.format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string(&DoctorReport { ok: failed == 0, checks })?)
+        }
+        OutputFormat::Text => {
+            for check in checks {
+                let label = match check.status {
+                    CheckStatus::Pass => paint("pass", Color::Green),
+                    CheckStatus::Warn => paint("warn", Color::Yellow),
+                    CheckStatus::Fail => paint("fail", Color::Red),
+                };
+                println!("[{}] {:<12}  {}", label, check.name, check.detail);
+                if let Some(hint) = &check.hint {
+                    println!("       {:<12}  hint: {}", "", hint);
+                }
+            }
         }
+    }
 
-        Ok(())
+    if failed > 0 {
+        let threshold = if fail_on == CheckStatus::Warn { "warn" } else { "fail" };
+        return Err(anyhow!("{} check(s) at or above the --fail-on {} threshold", failed, threshold));
     }
+    Ok(())
+}
 
-    fn add_files(&self, files: &[String]) -> Result<()> {
-        if self.dry_run {
-            info!("[DRY RUN] Would add files: {:?}", files);
-            return Ok(());
-        }
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {:?}", e);
+        process::exit(exit_code(&e));
+    }
+}
 
-        let output = Command::new("git")
-            .arg("add")
-            .args(files)
-            .output()
-            .map_err(|e| anyhow!("Failed to add files: {}", e))?;
+fn run() -> Result<()> {
+    let cli = Cli::parse();
 
-        if !output.status.success() {
-            let err_msg = String::from_utf8_lossy(&output.stderr);
-            error!("Add failed: {}", err_msg);
-            return Err(anyhow!("Add failed: {}", err_msg));
-        }
+    if let Commands::Completions { shell } = &cli.command {
+        print!("{}", generate_completions(*shell));
+        return Ok(());
+    }
 
-        Ok(())
+    let color = init_color(cli.no_color);
+
+    if cli.verbose {
+        env_logger::Builder::from_env(Env::default().default_filter_or("info"))
+            .write_style(if color { WriteStyle::Auto } else { WriteStyle::Never })
+            .init();
     }
+    // Without --verbose no logger is installed, so quiet mode only has to silence
+    // our own status lines; failures still reach stderr through main().
+    QUIET.store(cli.quiet, Ordering::Relaxed);
 
-    fn commit(&self, message: &str) -> Result<()> {
-        if self.dry_run {
-            info!("[DRY RUN] Would commit with message: {}", message);
-            return Ok(());
-        }
+    if let Commands::Doctor { fail_on } = &cli.command {
+        return run_doctor(&cli, *fail_on);
+    }
 
-        let output = Command::new("git")
-            .args(["commit", "-m", message])
-            .output()
-            .map_err(|e| anyhow!("Failed to commit: {}", e))?;
+    let mut config = load_config(cli.config.as_deref(), cli.migrate, !cli.no_global)?;
+    if let Some(timeout) = cli.timeout {
+        config.command_timeout_secs = Some(timeout);
+    }
+    if matches!(&cli.command, Commands::Commit(args) if args.tags) {
+        config.push_tags = true;
+    }
+    let git_ops = GitOps::new(
+        config,
+        cli.dry_run,
+        cli.remote.clone(),
+        cli.stream,
+        cli.yes,
+        cli.format == OutputFormat::Json,
+    );
+    git_ops.check_git_executable()?;
 
-        if !output.status.success() {
-            let err_msg = String::from_utf8_lossy(&output.stderr);
-            error!("Commit failed: {}", err_msg);
-            return Err(anyhow!("Commit failed: {}", err_msg));
+    if cli.command.requires_repo() {
+        if !git_ops.check_git_repo() {
+            error!("Not in a git repository");
+            return Err(GitError::NotARepo.into());
         }
 
-        Ok(())
+        if let Some(remote) = &cli.remote {
+            git_ops.ensure_remote_exists(remote)?;
+        }
     }
 
-    fn push(&self, branch: &str) -> Result<()> {
-        if self.dry_run {
-            info!("[DRY RUN] Would push to {}", branch);
-            return Ok(());
+    match &cli.command {
+        Commands::Commit(args) => run_commit(&git_ops, args, cli.yes)?,
+        Commands::Branch { cmd } => {
+            // A dry run already prints its plan as JSON.
+            let json =
+                cli.format == OutputFormat::Json && !git_ops.dry_run && !matches!(cmd, BranchCommands::List { .. });
+            match run_branch_command(&git_ops, cmd, cli.yes, cli.format) {
+                Ok(Some(result)) if json => println!("{}", serde_json::to_string(&result)?),
+                Ok(_) => {}
+                Err(e) if json => {
+                    let requested = match cmd {
+                        BranchCommands::Create { name, .. } | BranchCommands::Delete { name, .. } => Some(name.clone()),
+                        BranchCommands::Switch { name, .. } => name.clone(),
+                        BranchCommands::Rename { new, .. } => Some(new.clone()),
+                        BranchCommands::List { .. } => None,
+                    };
+                    let result = BranchOpResult {
+                        action: cmd.action(),
+                        branch: requested,
+                        from: None,
+                        ok: false,
+                        error: Some(format!("{:#}", e).trim_end().to_string()),
+                    };
+                    println!("{}", serde_json::to_string(&result)?);
+                    return Err(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Commands::Bisect { cmd } => match cmd {
+            BisectCommands::Start { good, bad } => print_bisect_progress(&git_ops.bisect_start(good, bad)?),
+            BisectCommands::Good { commit } => print_bisect_progress(&git_ops.bisect_mark("good", commit.as_deref())?),
+            BisectCommands::Bad { commit } => print_bisect_progress(&git_ops.bisect_mark("bad", commit.as_deref())?),
+            BisectCommands::Reset => git_ops.bisect_reset()?,
+            BisectCommands::Run { command } => {
+                if let Some(sha) = git_ops.bisect_run(command)? {
+                    // git's own summary may not end with a newline.
+                    println!();
+                    println!("{} {}", paint("First bad commit:", Color::Red), sha);
+                    println!("Run `git-automate bisect reset` to finish");
+                }
+            }
+        },
+        Commands::GitConfig { cmd } => match cmd {
+            GitConfigCommands::Get { key, global } => println!("{}", git_ops.git_config_get(key, *global)?),
+            GitConfigCommands::Set { key, value, global } => git_ops.git_config_set(key, value, *global)?,
+        },
+        Commands::Tag { cmd } => {
+            match cmd {
+                TagCommands::Create { name, message, push, sign, no_sign } => {
+                    let sign = *sign || (!*no_sign && message.is_some() && git_ops.sign_tags());
+                    git_ops.create_tag(name, message.as_deref(), sign)?;
+                    if *push {
+                        git_ops.push_tag(name)?;
+                    }
+                }
+                TagCommands::Delete { name } => git_ops.delete_tag(name)?,
+                TagCommands::List => {
+                    for tag in git_ops.list_tags()? {
+                        println!("{}", tag);
+                    }
+                }
+            }
+        }
+        Commands::Worktree { cmd } => match cmd {
+            WorktreeCommands::Add { path, branch } => git_ops.worktree_add(path, branch)?,
+            WorktreeCommands::Remove { path, force } => git_ops.worktree_remove(path, *force)?,
+            WorktreeCommands::List => {
+                let worktrees = git_ops.worktrees()?;
+                match cli.format {
+                    OutputFormat::Json => println!("{}", serde_json::to_string(&worktrees)?),
+                    OutputFormat::Text => {
+                        let width = worktrees.iter().map(|worktree| worktree.path.len()).max().unwrap_or(0);
+                        for worktree in &worktrees {
+                            let head = worktree.head.as_deref().map_or("", |head| &head[..head.len().min(7)]);
+                            let target = match &worktree.branch {
+                                _ if worktree.bare => String::from("(bare)"),
+                                Some(branch) => format!("[{}]", branch),
+                                None => String::from("(detached HEAD)"),
+                            };
+                            let mut flags = Vec::new();
+                            if worktree.locked {
+                                flags.push("locked");
+                            }
+                            if worktree.prunable {
+                                flags.push("prunable");
+                            }
+                            let line = format!("{:<width$}  {:<7}  {} {}", worktree.path, head, target, flags.join(" "));
+                            println!("{}", line.trim_end());
+                        }
+                    }
+                }
+            }
+        },
+        Commands::Remote { cmd } => {
+            match cmd {
+                RemoteCommands::List => println!("{}", git_ops.remote_urls()?),
+            }
+        }
+        Commands::Stash { cmd } => {
+            match cmd {
+                StashCommands::Save { message } => git_ops.stash_save(message.as_deref())?,
+                StashCommands::Pop => git_ops.stash_pop()?,
+                StashCommands::List => {
+                    for entry in git_ops.stash_list()? {
+                        println!("{}", entry);
+                    }
+                }
+                StashCommands::Drop { index } => git_ops.stash_drop(*index)?,
+            }
+        }
+        Commands::Fetch { all, prune } => {
+            let updates = git_ops.fetch(*all, *prune)?;
+            if !git_ops.dry_run {
+                if updates.is_empty() && !quiet() {
+                    println!("Already up to date");
+                }
+                for update in updates {
+                    println!("{}", update);
+                }
+            }
+        }
+        Commands::Sync { abort_on_conflict } => run_sync(&git_ops, *abort_on_conflict)?,
+        Commands::Watch { interval, debounce } => run_watch(&git_ops, *interval, *debounce)?,
+        Commands::Wip { push } => run_wip(&git_ops, *push)?,
+        Commands::Prune { dry_run_list, merged_into, remote_prune } => {
+            run_prune(&git_ops, *dry_run_list, merged_into.as_deref(), *remote_prune)?
+        }
+        Commands::Restore { files, staged } => run_restore(&git_ops, files, *staged, cli.yes)?,
+        Commands::Clean { directories, force, ignored } => {
+            run_clean(&git_ops, *directories, *force, *ignored, cli.yes)?
+        }
+        Commands::Changelog { since, output } => run_changelog(&git_ops, since.as_deref(), output.as_deref())?,
+        Commands::Release { version, no_push } => run_release(&git_ops, version, *no_push)?,
+        Commands::Squash { onto, message } => run_squash(&git_ops, onto, message.as_deref())?,
+        Commands::Pr { title, base, draft } => run_pr(&git_ops, title.as_deref(), base.as_deref(), *draft)?,
+        Commands::Rebase { onto, interactive } => git_ops.rebase(onto, *interactive)?,
+        Commands::Merge { branch, no_ff, ff_only, push } => {
+            git_ops.merge(branch, *no_ff, *ff_only)?;
+            if *push {
+                let current_branch = git_ops.get_current_branch()?;
+                git_ops.push(&current_branch, ForceMode::None, false)?;
+            }
+        }
+        Commands::CherryPick { commit, no_commit } => git_ops.cherry_pick(commit, *no_commit)?,
+        Commands::Undo { hard } => {
+            let confirmed = !*hard
+                || cli.yes
+                || git_ops.dry_run
+                || confirm("Hard reset will discard the last commit and all uncommitted changes. Continue?")?;
+            if !confirmed {
+                warn!("Undo cancelled");
+                return Ok(());
+            }
+            git_ops.undo_last_commit(*hard)?;
+        }
+        Commands::Reset { target, mode } => {
+            let confirmed = *mode != ResetMode::Hard
+                || cli.yes
+                || git_ops.dry_run
+                || confirm(&format!("Hard reset to {} will discard all uncommitted changes. Continue?", target))?;
+            if !confirmed {
+                warn!("Reset cancelled");
+                return Ok(());
+            }
+            git_ops.reset(target, *mode)?;
         }
+        Commands::Diff { staged, stat, no_pager } => {
+            let diff = git_ops.diff(*staged, *stat)?;
+            if !diff.is_empty() {
+                print_paged(&diff, *no_pager)?;
+            }
+        }
+        Commands::Log { count, oneline, since, until } => {
+            println!("{}", git_ops.log(count.unwrap_or(10), *oneline, since.as_deref(), until.as_deref())?);
+        }
+        Commands::Stats { since } => {
+            let stats = git_ops.stats(since.as_deref())?;
+            match cli.format {
+                OutputFormat::Json => println!("{}", serde_json::to_string(&stats)?),
+                OutputFormat::Text => {
+                    println!("Commits:      {}", stats.commits);
+                    println!("Contributors: {}", stats.contributors);
+                    println!("Lines:        +{} / -{}", stats.lines_added, stats.lines_removed);
+                    if !stats.most_changed_files.is_empty() {
+                        println!();
+                        println!("{:>8}  {:>7}  Most changed files", "lines", "commits");
+                        for file in &stats.most_changed_files {
+                            println!("{:>8}  {:>7}  {}", file.lines_changed, file.commits, file.path);
+                        }
+                    }
+                }
+            }
+        }
+        Commands::Contributors { since, until } => {
+            let contributors = git_ops.contributors(since.as_deref(), until.as_deref())?;
+            match cli.format {
+                OutputFormat::Text if contributors.is_empty() => println!("no contributors yet"),
+                OutputFormat::Text => {
+                    for (rank, (name, commits)) in contributors.iter().enumerate() {
+                        println!("{:>3}. {:>5}  {}", rank + 1, commits, name);
+                    }
+                }
+                OutputFormat::Json => {
+                    let entries: Vec<_> = contributors
+                        .iter()
+                        .map(|(name, commits)| serde_json::json!({ "name": name, "commits": commits }))
+                        .collect();
+                    println!("{}", serde_json::to_string(&entries)?);
+                }
+            }
+        }
+        Commands::Blame { file, range } => {
+            let porcelain = cli.format == OutputFormat::Json;
+            let output = git_ops.blame(file, range.as_deref(), porcelain)?;
+            if porcelain {
+                println!("{}", serde_json::to_string(&parse_blame_porcelain(&output))?);
+            } else {
+                println!("{}", output);
+            }
+        }
+        Commands::Clone { url, dest, init_config } => {
+            let dest = match dest {
+                Some(dest) => PathBuf::from(dest),
+                None => clone_destination(url)?,
+            };
+            git_ops.clone_repo(url, &dest)?;
 
-        let output = Command::new("git")
-            .args(["push", &self.config.default_remote, branch])
-            .output()
-            .map_err(|e| anyhow!("Failed to push: {}", e))?;
+            if *init_config {
+                let config_path = dest.join("git-automate.toml");
+                if git_ops.dry_run {
+                    info!("[DRY RUN] Would write default config to {}", config_path.display());
+                } else {
+                    fs::write(&config_path, toml::to_string_pretty(&Config::default())?)?;
+                    info!("Wrote default config to {}", config_path.display());
+                }
+            }
+        }
+        Commands::Init { path, force, interactive } => {
+            if path.exists() && !*force {
+                return Err(anyhow!("Config already exists at {}; pass --force to overwrite it", path.display()));
+            }
 
-        if !output.status.success() {
-            let err_msg = String::from_utf8_lossy(&output.stderr);
-            error!("Push failed: {}", err_msg);
-            return Err(anyhow!("Push failed: {}", err_msg));
+            let config = if !*interactive {
+                Config::default()
+            } else if io::stdin().is_terminal() {
+                run_init_wizard(&git_ops)?
+            } else {
+                if !quiet() {
+                    println!("stdin is not a terminal; writing the default configuration instead");
+                }
+                Config::default()
+            };
+            let toml = toml::to_string_pretty(&config)?;
+            if git_ops.dry_run {
+                info!("[DRY RUN] Would write config to {}", path.display());
+                if git_ops.plan.is_none() {
+                    print!("{}", toml);
+                }
+            } else {
+                fs::write(path, toml).map_err(|e| anyhow!("Failed to write config {}: {}", path.display(), e))?;
+                info!("Initialized configuration file at {}", path.display());
+            }
         }
+        // Handled before loading configuration so it works anywhere.
+        Commands::Completions { .. } | Commands::Doctor { .. } => {}
+        Commands::History { count } => {
+            let entries = git_ops.read_history(*count)?;
+            if entries.is_empty() && !quiet() {
+                println!("No operations recorded yet");
+            }
+            for entry in entries {
+                println!("{}", entry);
+            }
+        }
+        Commands::Status { short } => {
+            let StatusSnapshot { branch: current_branch, has_changes, entries, upstream } = git_ops.status_snapshot()?;
 
-        Ok(())
+            match cli.format {
+                OutputFormat::Text if *short => {
+                    let changes = match entries.len() {
+                        0 => paint("clean", Color::Green),
+                        1 => paint("1 changed file", Color::Yellow),
+                        count => paint(&format!("{} changed files", count), Color::Yellow),
+                    };
+                    println!("{} [{}] {}", paint(&current_branch, Color::Green), upstream, changes);
+                }
+                OutputFormat::Text => {
+                    println!("Current branch: {}", paint(&current_branch, Color::Green));
+                    match &upstream.upstream {
+                        Some(name) => println!("Upstream: {} ({})", name, upstream),
+                        None => println!("Upstream: {}", upstream),
+                    }
+                    let changes = if has_changes { paint("true", Color::Yellow) } else { paint("false", Color::Green) };
+                    println!("Has uncommitted changes: {}", changes);
+                    print_status_entries(&entries);
+                }
+                OutputFormat::Json => {
+                    let tracked = upstream.upstream.is_some();
+                    let report = StatusReport {
+                        branch: current_branch,
+                        has_changes,
+                        ahead: tracked.then_some(upstream.ahead),
+                        behind: tracked.then_some(upstream.behind),
+                        upstream: upstream.upstream,
+                        entries,
+                    };
+                    println!("{}", serde_json::to_string(&report)?);
+                }
+            }
+        }
     }
 
-    fn create_branch(&self, name: &str) -> Result<()> {
-        if self.dry_run {
-            info!("[DRY RUN] Would create branch: {}", name);
-            return Ok(());
-        }
+    let plan = git_ops.take_plan();
+    if !plan.is_empty() {
+        println!("{}", serde_json::to_string(&plan)?);
+    }
 
-        let output = Command::new("git")
-            .args(["checkout", "-b", name])
-            .output()
-            .map_err(|e| anyhow!("Failed to create branch: {}", e))?;
+    Ok(())
+}
 
-        if !output.status.success() {
-            let err_msg = String::from_utf8_lossy(&output.stderr);
-            error!("Branch creation failed: {}", err_msg);
-            return Err(anyhow!("Branch creation failed: {}", err_msg));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_commands_are_not_logged() {
+        let cases: &[(&[&str], bool)] = &[
+            (&["rev-parse", "HEAD"], true),
+            (&["for-each-ref", "--format=%(refname)", "refs/heads"], true),
+            (&["ls-remote", "--heads", "origin"], true),
+            (&["-C", "../wt", "status", "--porcelain"], true),
+            (&["worktree", "list", "--porcelain"], true),
+            (&["remote", "prune", "--dry-run", "origin"], true),
+            (&["remote", "prune", "origin"], false),
+            (&["branch", "feature"], false),
+            (&["tag", "-a", "v1.0", "-m", "release"], false),
+            (&["push", "origin", "main"], false),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(is_query_command(args), *expected, "{:?}", args);
         }
+    }
 
-        Ok(())
+    #[test]
+    fn watch_waits_for_the_snapshot_to_settle() {
+        let debounce = Duration::from_secs(5);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut state = WatchDebounce::default();
+
+        assert!(!state.observe(None, at(0), debounce));
+        assert!(!state.observe(Some(1), at(1), debounce));
+        assert!(!state.observe(Some(1), at(5), debounce));
+        // Same status, different content: the clock starts over.
+        assert!(!state.observe(Some(2), at(6), debounce));
+        assert!(!state.observe(Some(2), at(10), debounce));
+        assert!(state.observe(Some(2), at(11), debounce));
     }
 
-    fn switch_branch(&self, name: &str) -> Result<()> {
-        if self.dry_run {
-            info!("[DRY RUN] Would switch to branch: {}", name);
-            return Ok(());
+    #[test]
+    fn watch_does_not_retry_an_attempted_snapshot() {
+        let debounce = Duration::ZERO;
+        let start = Instant::now();
+        let mut state = WatchDebounce::default();
+
+        assert!(!state.observe(Some(1), start, debounce));
+        assert!(state.observe(Some(1), start, debounce));
+        state.attempt();
+        assert!(!state.observe(Some(1), start, debounce));
+        assert!(!state.observe(Some(1), start, debounce));
+
+        // A further edit is picked up again.
+        assert!(!state.observe(Some(3), start, debounce));
+        assert!(state.observe(Some(3), start, debounce));
+        state.attempt();
+        state.committed();
+        assert!(!state.observe(Some(3), start, debounce));
+        assert!(state.observe(Some(3), start, debounce));
+    }
+
+    #[test]
+    fn slugify_normalizes_descriptions() {
+        let cases = [
+            ("Login timeout", "login-timeout"),
+            ("  --Fix the   API!!  ", "fix-the-api"),
+            ("naïve Café über", "naïve-café-über"),
+            ("ЗАДАЧА 42", "задача-42"),
+            ("a/b_c.d", "a-b-c-d"),
+            ("---", ""),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(slugify(text), expected, "{:?}", text);
         }
+    }
 
-        let output = Command::new("git")
-            .args(["checkout", name])
-            .output()
-            .map_err(|e| anyhow!("Failed to switch branch: {}", e))?;
+    #[test]
+    fn branch_type_goes_after_the_prefix() {
+        let cases = [
+            (Some("alice/"), Some("fix"), "login-timeout", "alice/fix/login-timeout"),
+            (Some("alice/"), None, "login-timeout", "alice/login-timeout"),
+            (None, Some("feat"), "search", "feat/search"),
+            (None, None, "search", "search"),
+            // A namespace typed by the user replaces the prefix.
+            (Some("alice/"), Some("fix"), "team/login", "fix/team/login"),
+            (Some("alice/"), None, "team/login", "team/login"),
+        ];
+        for (prefix, branch_type, name, expected) in cases {
+            assert_eq!(compose_branch_name(prefix, branch_type, name), expected);
+        }
+    }
 
-        if !output.status.success() {
-            let err_msg = String::from_utf8_lossy(&output.stderr);
-            error!("Branch switch failed: {}", err_msg);
-            return Err(anyhow!("Branch switch failed: {}", err_msg));
+    #[test]
+    fn exit_codes_follow_the_error_chain() {
+        let cases: Vec<(GitError, i32)> = vec![
+            (GitError::NotARepo, 2),
+            (GitError::NothingToCommit, 3),
+            (GitError::MergeConflict { action: "Merge".into(), files: vec!["a.txt".into()], hint: String::new() }, 4),
+            (GitError::PushRejected("rejected".into()), 5),
+            (GitError::GitNotFound { path: None }, 6),
+            (GitError::CommandFailed { action: "Rebase onto main".into(), stderr: "fatal".into() }, 1),
+            (GitError::Timeout { secs: 5 }, 1),
+        ];
+        for (error, expected) in cases {
+            let error = anyhow::Error::from(error).context("while running the command");
+            assert_eq!(exit_code(&error), expected, "{:?}", error);
         }
+        assert_eq!(exit_code(&anyhow!("plain failure")), 1);
+    }
 
-        Ok(())
+    #[test]
+    fn commit_failures_are_command_failed() {
+        let git_ops = GitOps::new(Config::default(), false, None, false, true, false);
+        let unsigned = CommitOptions { no_verify: false, sign: false, allow_empty: false, author: None, sign_off: None };
+        let error = git_ops.commit_failure("Commit", &unsigned, "fatal: bad object\n");
+        assert!(matches!(
+            error.downcast_ref::<GitError>(),
+            Some(GitError::CommandFailed { action, stderr }) if action == "Commit" && stderr == "fatal: bad object"
+        ));
+
+        let signed = CommitOptions { sign: true, ..unsigned };
+        let error = git_ops.commit_failure("Commit", &signed, "error: gpg failed to sign the data");
+        assert!(format!("{}", error).contains("check your signing setup"));
+        assert!(error
+            .chain()
+            .any(|cause| matches!(cause.downcast_ref::<GitError>(), Some(GitError::CommandFailed { .. }))));
+        assert_eq!(exit_code(&error), 1);
     }
 
-    fn delete_branch(&self, name: &str) -> Result<()> {
-        if self.dry_run {
-            info!("[DRY RUN] Would delete branch: {}", name);
-            return Ok(());
+    #[test]
+    fn only_connection_failures_are_retried() {
+        let cases = [
+            ("fatal: unable to access 'https://example.com/r.git/': Could not resolve host: example.com", true),
+            ("ssh: connect to host example.com port 22: Connection refused", true),
+            ("fatal: the remote end hung up unexpectedly", true),
+            ("error: RPC failed; curl 18 transfer closed\nfatal: early EOF", true),
+            ("fatal: unable to access 'https://example.com/r.git/': Operation timed out after 30000 ms", true),
+            ("fatal: Authentication failed for 'https://example.com/r.git/'", false),
+            ("fatal: unable to access 'https://example.com/r.git/': The requested URL returned error: 403", false),
+            ("fatal: could not read Username for 'https://example.com': terminal prompts disabled", false),
+            ("git@example.com: Permission denied (publickey).\nfatal: Could not read from remote repository.", false),
+            ("! [rejected] main -> main (non-fast-forward)", false),
+        ];
+        for (message, expected) in cases {
+            assert_eq!(is_network_error(message), expected, "{}", message);
         }
+    }
 
-        let output = Command::new("git")
-            .args(["branch", "-d", name])
-            .output()
-            .map_err(|e| anyhow!("Failed to delete branch: {}", e))?;
+    #[test]
+    fn branch_names_follow_git_ref_rules() {
+        let invalid = [
+            "",
+            "-feature",
+            "/feature",
+            "feature/",
+            "feature//login",
+            "feature..login",
+            "feature@{1}",
+            "@",
+            "feature.",
+            "feature.lock",
+            "feature.lock/login",
+            "feature/.hidden",
+            "my feature",
+            "feature\tlogin",
+            "feature\u{7f}",
+            "feature~1",
+            "feature^",
+            "feature:login",
+            "feature?",
+            "feature*",
+            "feature[1]",
+            "feature\\login",
+        ];
+        for name in invalid {
+            assert!(validate_branch_name(name).is_err(), "{:?} should be rejected", name);
+        }
 
-        if !output.status.success() {
-            let err_msg = String::from_utf8_lossy(&output.stderr);
-            error!("Branch deletion failed: {}", err_msg);
-            return Err(anyhow!("Branch deletion failed: {}", err_msg));
+        let valid = ["main", "feature/login-timeout", "fix/JIRA-123_retry", "release/1.2.0", "alice@home", "über/straße"];
+        for name in valid {
+            assert!(validate_branch_name(name).is_ok(), "{:?} should be accepted", name);
         }
+    }
 
-        Ok(())
+    #[test]
+    fn status_lines_parse_into_entries() {
+        use FileState::*;
+        let cases = [
+            (" M src/main.rs", "src/main.rs", None, None, Some(Modified)),
+            ("M  src/main.rs", "src/main.rs", None, Some(Modified), None),
+            ("AM new.rs", "new.rs", None, Some(Added), Some(Modified)),
+            (" D gone.txt", "gone.txt", None, None, Some(Deleted)),
+            ("R  old.rs -> new.rs", "new.rs", Some("old.rs"), Some(Renamed), None),
+            ("RM \"old name\" -> \"new name\"", "new name", Some("old name"), Some(Renamed), Some(Modified)),
+            ("?? \"notes for me.md\"", "notes for me.md", None, None, Some(Untracked)),
+            ("?? \"caf\\303\\251 \\\"menu\\\".txt\"", "café \"menu\".txt", None, None, Some(Untracked)),
+            ("!! target/", "target/", None, None, Some(Ignored)),
+            ("UU conflict.rs", "conflict.rs", None, Some(Unmerged), None),
+            ("AA both-added.rs", "both-added.rs", None, Some(Unmerged), None),
+            // Only renames and copies carry an arrow; elsewhere it is part of the name.
+            ("?? a -> b", "a -> b", None, None, Some(Untracked)),
+        ];
+        for (line, path, original_path, staged, unstaged) in cases {
+            let entry = StatusEntry::parse(line).unwrap();
+            assert_eq!(entry.path, path, "{}", line);
+            assert_eq!(entry.original_path.as_deref(), original_path, "{}", line);
+            assert_eq!(entry.staged, staged, "{}", line);
+            assert_eq!(entry.unstaged, unstaged, "{}", line);
+        }
+        assert!(StatusEntry::parse("M").is_none());
     }
-}
 
-fn load_config() -> Result<Config> {
-    let config_path = PathBuf::from("git-automate.toml");
-    if !config_path.exists() {
-        return Ok(Config::default());
+    #[test]
+    fn globs_match_like_gitignore() {
+        let cases = [
+            ("*.env", ".env", true),
+            ("*.env", "config/prod.env", true),
+            ("*.env", "prod.env.example", false),
+            ("secrets/*", "secrets/key.pem", true),
+            ("secrets/*", "app/secrets/key.pem", false),
+            ("/build", "build/out.o", true),
+            ("/build", "src/build", false),
+            ("file?.txt", "file1.txt", true),
+            ("file?.txt", "file10.txt", false),
+            ("file?.txt", "file/.txt", false),
+            ("**/fixtures/*.json", "fixtures/user.json", true),
+            ("**/fixtures/*.json", "tests/api/fixtures/user.json", true),
+            ("logs/**", "logs/2024/01/app.log", true),
+            ("a/**/b", "a/x/y/b", true),
+            ("a/**/b", "a/b", true),
+            ("id_[rd]sa", "id_rsa", true),
+            ("id_[rd]sa", "id_dsa", true),
+            ("id_[rd]sa", "id_ecdsa", false),
+            ("report[0-9].csv", "report7.csv", true),
+            ("report[0-9].csv", "reportx.csv", false),
+            ("[!.]*.key", "server.key", true),
+            ("[!.]*.key", ".hidden.key", false),
+            ("[]x].txt", "].txt", true),
+            ("[unclosed", "[unclosed", true),
+            ("release.*", "release.notes", true),
+            ("release.*", "releaseXnotes", false),
+            ("(draft)+.md", "(draft)+.md", true),
+            ("(draft)+.md", "draftdraft.md", false),
+            ("\\*literal", "*literal", true),
+            ("\\*literal", "xliteral", false),
+        ];
+        for (pattern, path, expected) in cases {
+            let regex = glob_to_regex(pattern).unwrap();
+            assert_eq!(regex.is_match(path), expected, "{:?} against {:?}", pattern, path);
+        }
+        assert!(glob_to_regex("[z-a]").is_err());
     }
 
-    let config_str = fs::read_to_string(config_path)
-        .map_err(|e| anyhow!("Failed to read config file: {}", e))?;
-    let config = toml::from_str(&config_str)
-        .map_err(|e| anyhow!("Failed to parse config file: {}", e))?;
+    #[test]
+    fn lint_reports_each_rule() {
+        let config = Config {
+            max_subject_length: Some(20),
+            forbid_subject_period: true,
+            require_conventional: true,
+            ..Config::default()
+        };
+        let cases = [
+            ("fix: short subject", None),
+            ("fix(api): retry\n\nLonger body text that can be as long as it likes.", None),
+            ("fix: this subject is far too long", Some("over max_subject_length")),
+            ("fix: ends with dot.", Some("ends with a period")),
+            ("fix: subject\nbody right below", Some("blank line")),
+            ("Fix the thing", Some("not a conventional commit")),
+            ("wat: unknown type", Some("Unknown commit type")),
+            ("fix: ", Some("no description")),
+        ];
+        for (message, expected) in cases {
+            match (lint_commit_message(message, &config), expected) {
+                (Ok(()), None) => {}
+                (Err(e), Some(expected)) => assert!(e.to_string().contains(expected), "{:?}: {}", message, e),
+                (result, expected) => panic!("{:?}: got {:?}, expected {:?}", message, result.err(), expected),
+            }
+        }
+    }
 
-    Ok(config)
-}
+    #[test]
+    fn lint_rules_are_off_by_default() {
+        let config = Config::default();
+        assert!(lint_commit_message("Ship it.", &config).is_ok());
+        assert!(lint_commit_message(&"x".repeat(200), &config).is_ok());
+        // The subject/body separator is git's own format, so it is always checked.
+        assert!(lint_commit_message("Subject\nBody", &config).is_err());
+    }
 
-fn generate_commit_message(template: &str, conventional: bool) -> String {
-    let mut generator = Generator::default();
-    let name = generator.next().unwrap();
-    
-    if conventional {
-        format!("feat: {}", name)
-    } else {
-        name
+    #[test]
+    fn project_config_overrides_global_keys() {
+        let table = |text: &str| Some(toml::from_str::<toml::Table>(text).unwrap());
+        let global = table("default_remote = \"upstream\"\nauto_pull = false\nwip_prefix = \"SAVE\"\n");
+        let local = table("default_remote = \"origin\"\nsign_commits = true\n");
+
+        let config = merge_config_tables(global.clone(), local).unwrap();
+        assert_eq!(config.default_remote, "origin");
+        assert!(config.sign_commits);
+        assert!(!config.auto_pull);
+        assert_eq!(config.wip_prefix, "SAVE");
+        assert_eq!(config.commit_template, Config::default().commit_template);
+
+        let config = merge_config_tables(global, None).unwrap();
+        assert_eq!(config.default_remote, "upstream");
+        let config = merge_config_tables(None, None).unwrap();
+        assert_eq!(config.default_remote, Config::default().default_remote);
     }
-}
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-    
-    if cli.verbose {
-        env_logger::Builder::from_env(Env::default().default_filter_or("info"))
-            .init();
+    #[test]
+    fn no_global_skips_the_user_config() {
+        assert_eq!(global_config_file(false, None), None);
+        assert_eq!(global_config_file(false, Some(Path::new("git-automate.toml"))), None);
     }
 
-    let config = load_config()?;
-    let git_ops = GitOps::new(config, cli.dry_run);
+    #[test]
+    fn v1_configs_migrate_to_the_current_version() {
+        let v1 = "default_remote = \"upstream\"\nauto_pull = false\nprotected_branches = [\"trunk\"]\n";
+        let mut config: Config = toml::from_str(v1).unwrap();
+        assert_eq!(config.version, 1);
 
-    if !git_ops.check_git_repo() {
-        error!("Not in a git repository");
-        return Err(anyhow::anyhow!("Not in a git repository"));
+        assert!(config.migrate());
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.default_remote, "upstream");
+        assert!(!config.auto_pull);
+        assert_eq!(config.protected_branches, ["trunk"]);
+        assert!(!config.migrate());
+
+        let path = std::env::temp_dir().join(format!("git-automate-migrate-{}.toml", process::id()));
+        fs::write(&path, v1).unwrap();
+        let table = read_config_table(&path, true);
+        let written = fs::read_to_string(&path);
+        let _ = fs::remove_file(&path);
+        assert_eq!(table.unwrap()["version"].as_integer(), Some(i64::from(CONFIG_VERSION)));
+        let written: Config = toml::from_str(&written.unwrap()).unwrap();
+        assert_eq!(written.version, CONFIG_VERSION);
+        assert_eq!(written.default_remote, "upstream");
     }
 
-    match &cli.command {
-        Commands::Commit { message, files, conventional } => {
-            let files = files.clone().unwrap_or_else(|| vec![String::from(".")]);
+    #[test]
+    fn changelog_groups_commits_by_type() {
+        let commits: Vec<(String, String)> = [
+            ("a1", "fix(push): retry on timeouts"),
+            ("b2", "Update README"),
+            ("c3", "feat: add worktree commands"),
+            ("d4", "docs: explain exit codes"),
+            ("e5", "feat(config)!: drop the legacy keys"),
+            ("f6", "fix: handle detached HEAD"),
+        ]
+        .into_iter()
+        .map(|(sha, subject)| (sha.to_string(), subject.to_string()))
+        .collect();
 
-            
-            if git_ops.config.auto_pull {
-                git_ops.pull()?;
-            }
+        let expected = "\
+# v1.2.0
 
-            git_ops.add_files(&files)?;
+## Breaking Changes
 
-            if !git_ops.has_changes()? {
-                warn!("No changes to commit");
-                return Ok(());
-            }
+- **config:** drop the legacy keys (e5)
+
+## Features
+
+- add worktree commands (c3)
+- **config:** drop the legacy keys (e5)
+
+## Fixes
+
+- **push:** retry on timeouts (a1)
+- handle detached HEAD (f6)
 
-            let commit_msg = message.clone()
-                .unwrap_or_else(|| generate_commit_message(&git_ops.config.commit_template, *conventional));
-            
-            git_ops.commit(&commit_msg)?;
+## Documentation
 
-            let current_branch = git_ops.get_current_branch()?;
-            git_ops.push(&current_branch)?;
+- explain exit codes (d4)
 
-            info!("Successfully committed and pushed changes");
+## Other
+
+- Update README (b2)
+";
+        assert_eq!(render_changelog("v1.2.0", &commits), expected);
+        assert_eq!(render_changelog("v1.2.1", &[]), "# v1.2.1\n\nNo changes.\n");
+    }
+
+    #[test]
+    fn diff_stats_become_short_descriptions() {
+        let stat = |paths: &[&str]| {
+            let mut stat: String = paths.iter().map(|path| format!(" {} | 3 ++-\n", path)).collect();
+            stat.push_str(&format!(" {} files changed, 6 insertions(+), 3 deletions(-)", paths.len()));
+            stat
+        };
+        let cases: &[(&[&str], Option<&str>)] = &[
+            (&[], None),
+            (&["src/main.rs"], Some("update src/main.rs")),
+            (&["src/git/push.rs", "src/git/pull.rs"], Some("update 2 files in src/git/")),
+            (&["README.md", "docs/guide.md"], Some("update 2 .md files")),
+            (&["src/a.rs", "src/b.rs", "Cargo.toml"], Some("update 3 files, mostly in src/")),
+            (&["notes.txt", "build.rs"], Some("update 2 files")),
+            (&["src/{old.rs => new.rs}"], Some("update src/new.rs")),
+            (&["{src => lib}/util.rs", "lib/mod.rs"], Some("update 2 files in lib/")),
+            (&["old.md => new.md"], Some("update new.md")),
+            (&["src/{nested => }/a.rs"], Some("update src/a.rs")),
+            (&["logo.png"], Some("update logo.png")),
+        ];
+        for (paths, expected) in cases {
+            assert_eq!(describe_diff_stat(&stat(paths)).as_deref(), *expected, "{:?}", paths);
         }
-        Commands::Branch { cmd } => {
-            match cmd {
-                BranchCommands::Create { name } => git_ops.create_branch(name)?,
-                BranchCommands::Switch { name } => git_ops.switch_branch(name)?,
-                BranchCommands::Delete { name } => git_ops.delete_branch(name)?,
-            }
+    }
+
+    #[test]
+    fn tickets_come_from_the_branch_name() {
+        let with_group = Regex::new(r"(?i)\b([A-Z]+-\d+)\b").unwrap();
+        let whole_match = Regex::new(r"#\d+").unwrap();
+        let cases = [
+            (&with_group, "feature/PROJ-123-login", Some("PROJ-123")),
+            (&with_group, "alice/fix/proj-9", Some("proj-9")),
+            (&with_group, "main", None),
+            (&whole_match, "fix/#42-crash", Some("#42")),
+        ];
+        for (pattern, branch, expected) in cases {
+            assert_eq!(extract_ticket(pattern, branch).as_deref(), expected, "{}", branch);
         }
-        Commands::Init => {
-            let config = Config::default();
-            let toml = toml::to_string_pretty(&config)?;
-            fs::write("git-automate.toml", toml)?;
-            info!("Initialized configuration file");
+    }
+
+    #[test]
+    fn tickets_are_added_once() {
+        let cases = [
+            ("Fix login", "prefix", "PROJ-1: Fix login"),
+            ("Fix login\n\nDetails", "suffix", "Fix login (PROJ-1)\n\nDetails"),
+            ("Fix login", "trailer", "Fix login\n\nRefs: PROJ-1"),
+            ("PROJ-1: Fix login", "prefix", "PROJ-1: Fix login"),
+            ("Fix login\n\nRefs: PROJ-1", "trailer", "Fix login\n\nRefs: PROJ-1"),
+            ("Follow up on PROJ-12", "suffix", "Follow up on PROJ-12 (PROJ-1)"),
+        ];
+        for (message, placement, expected) in cases {
+            assert_eq!(apply_ticket(message, "PROJ-1", placement), expected, "{:?} / {}", message, placement);
         }
-        Commands::Status => {
-            let current_branch = git_ops.get_current_branch()?;
-            let has_changes = git_ops.has_changes()?;
-            
-            println!("Current branch: {}", current_branch);
-            println!("Has uncommitted changes: {}", has_changes);
+    }
+
+    #[test]
+    fn require_ticket_rejects_branches_without_one() {
+        let config = Config {
+            ticket_pattern: Some(String::from(r"[A-Z]+-\d+")),
+            require_ticket: true,
+            ..Config::default()
+        };
+        assert_eq!(branch_ticket(&config, "feature/PROJ-7-login").unwrap().as_deref(), Some("PROJ-7"));
+        let error = branch_ticket(&config, "feature/login").unwrap_err();
+        assert!(error.to_string().contains("does not match ticket_pattern"));
+
+        let optional = Config { require_ticket: false, ..config };
+        assert_eq!(branch_ticket(&optional, "feature/login").unwrap(), None);
+        let invalid = Config { ticket_pattern: Some(String::from("([")), ..Config::default() };
+        assert!(branch_ticket(&invalid, "feature/login").is_err());
+    }
+
+    #[test]
+    fn env_booleans_accept_common_spellings() {
+        let cases = [
+            ("1", Some(true)),
+            ("true", Some(true)),
+            (" YES ", Some(true)),
+            ("on", Some(true)),
+            ("0", Some(false)),
+            ("False", Some(false)),
+            ("no", Some(false)),
+            ("off", Some(false)),
+            ("", Some(false)),
+            ("maybe", None),
+            ("2", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_env_bool("GIT_AUTOMATE_AUTO_PULL", value).ok(), expected, "{:?}", value);
         }
+        let error = parse_env_bool("GIT_AUTOMATE_AUTO_PULL", "maybe").unwrap_err();
+        assert!(error.to_string().contains("GIT_AUTOMATE_AUTO_PULL"));
     }
 
-    Ok(())
-}
+    #[test]
+    fn env_variables_override_the_config() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+        };
+
+        let mut config = Config { git_path: Some(String::from("/usr/bin/git")), ..Config::default() };
+        config
+            .apply_overrides(env(&[
+                ("GIT_AUTOMATE_REMOTE", "upstream"),
+                ("GIT_AUTOMATE_AUTO_PULL", "off"),
+                ("GIT_AUTOMATE_COMMIT_TEMPLATE", "wip: {}"),
+                ("GIT_AUTOMATE_GIT_PATH", ""),
+            ]))
+            .unwrap();
+        assert_eq!(config.default_remote, "upstream");
+        assert!(!config.auto_pull);
+        assert_eq!(config.commit_template, "wip: {}");
+        assert_eq!(config.git_path, None);
+
+        let mut config = Config::default();
+        config.apply_overrides(env(&[])).unwrap();
+        assert_eq!(config.default_remote, Config::default().default_remote);
+        assert_eq!(config.auto_pull, Config::default().auto_pull);
+
+        assert!(Config::default().apply_overrides(env(&[("GIT_AUTOMATE_AUTO_PULL", "sometimes")])).is_err());
+    }
+
+    #[test]
+    fn empty_commit_behavior_outcomes() {
+        use EmptyCommit::*;
+        let cases = [
+            ("skip", false, false, Skip),
+            ("error", false, false, Fail),
+            ("allow-empty", false, false, Create),
+            ("skip", true, false, Create),
+            ("error", true, false, Create),
+            ("skip", false, true, Fail),
+            ("allow-empty", false, true, Fail),
+        ];
+        for (behavior, allow_empty, require_changes, expected) in cases {
+            assert_eq!(
+                empty_commit_outcome(behavior, allow_empty, require_changes),
+                expected,
+                "{} --allow-empty={} --require-changes={}",
+                behavior,
+                allow_empty,
+                require_changes
+            );
+        }
+    }
+
+    #[test]
+    fn pull_flags_override_auto_pull() {
+        let cases = [
+            (true, false, false, true),
+            (false, false, false, false),
+            (false, true, false, true),
+            (true, false, true, false),
+            (true, true, false, true),
+            (false, false, true, false),
+        ];
+        for (auto_pull, pull, no_pull, expected) in cases {
+            let (decision, reason) = pull_decision(auto_pull, pull, no_pull);
+            assert_eq!(decision, expected, "auto_pull={} --pull={} --no-pull={}", auto_pull, pull, no_pull);
+            assert_eq!(reason.starts_with("--"), pull || no_pull);
+        }
+    }
+
+    #[test]
+    fn network_failures_are_retried_with_backoff() {
+        let run = |retries: u32, failures: usize, message: &'static str| {
+            let mut calls = 0;
+            let mut delays = Vec::new();
+            let result = retry_network_errors(
+                "Push",
+                retries,
+                || {
+                    calls += 1;
+                    if calls <= failures { Err(anyhow!(message)) } else { Ok(calls) }
+                },
+                |delay| delays.push(delay.as_secs()),
+            );
+            (result.map_err(|e| e.to_string()), calls, delays)
+        };
+        let timeout = "fatal: unable to access 'https://example.com/r.git/': Connection timed out";
+
+        assert_eq!(run(3, 0, timeout), (Ok(1), 1, vec![]));
+        assert_eq!(run(3, 2, timeout), (Ok(3), 3, vec![1, 2]));
+        assert_eq!(run(3, 3, timeout), (Ok(4), 4, vec![1, 2, 4]));
+        assert_eq!(run(2, 5, timeout), (Err(timeout.to_string()), 3, vec![1, 2]));
+        assert_eq!(run(0, 1, timeout), (Err(timeout.to_string()), 1, vec![]));
+        assert_eq!(run(7, 7, timeout).2, vec![1, 2, 4, 8, 16, 32, 32]);
+
+        let rejected = "! [rejected] main -> main (non-fast-forward)";
+        assert_eq!(run(3, 1, rejected), (Err(rejected.to_string()), 1, vec![]));
+    }
 
+    #[test]
+    fn protected_branches_match_exactly() {
+        let defaults = Config::default().protected_branches;
+        let custom = vec![String::from("trunk"), String::from("release/1.0")];
+        let cases = [
+            (&defaults, "main", true),
+            (&defaults, "master", true),
+            (&defaults, "main2", false),
+            (&defaults, "feature/main", false),
+            (&defaults, "Main", false),
+            (&custom, "trunk", true),
+            (&custom, "release/1.0", true),
+            (&custom, "release/1.1", false),
+            (&custom, "main", false),
+        ];
+        for (protected, branch, expected) in cases {
+            assert_eq!(is_protected_branch(branch, protected), expected, "{} in {:?}", branch, protected);
+        }
+        assert!(!is_protected_branch("main", &[]));
+    }
+
+    #[test]
+    fn amend_keeps_the_message_unless_given_one() {
+        assert_eq!(amend_args(None), ["commit", "--amend", "--no-edit"]);
+        assert_eq!(amend_args(Some("Fix typo\n\nDetails")), ["commit", "--amend", "-m", "Fix typo\n\nDetails"]);
+    }
+
+    #[test]
+    fn prune_never_deletes_current_target_or_protected_branches() {
+        let branches = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        let protected = branches(&["main", "master", "release"]);
+        let cases = [
+            (
+                &["main", "feature/a", "feature/b"][..],
+                "main",
+                "main",
+                &["feature/a", "feature/b"][..],
+                &["main (current or target branch)"][..],
+            ),
+            (
+                &["develop", "feature/a", "main", "release"],
+                "feature/a",
+                "develop",
+                &[],
+                &[
+                    "develop (current or target branch)",
+                    "feature/a (current or target branch)",
+                    "main (protected)",
+                    "release (protected)",
+                ],
+            ),
+            (&["main2", "hotfix"], "main", "main", &["main2", "hotfix"], &[]),
+            (&[], "main", "main", &[], &[]),
+        ];
+        for (merged, current, target, deletable, skipped) in cases {
+            let (actual_deletable, actual_skipped) =
+                classify_merged_branches(branches(merged), current, target, &protected);
+            assert_eq!(actual_deletable, deletable, "{:?} on {} into {}", merged, current, target);
+            assert_eq!(actual_skipped, skipped, "{:?} on {} into {}", merged, current, target);
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_REPO: AtomicUsize = AtomicUsize::new(0);
+
+/// A throwaway repository with one commit on a `feature` branch, removed again on drop.
+struct TestRepo {
+    dir: PathBuf,
+}
+
+impl TestRepo {
+    fn new(config: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!(
+            "git-automate-test-{}-{}",
+            std::process::id(),
+            NEXT_REPO.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let repo = TestRepo { dir };
+        repo.git(&["init", "-q"]);
+        repo.git(&["config", "user.name", "Test User"]);
+        repo.git(&["config", "user.email", "test@example.com"]);
+        repo.git(&["config", "commit.gpgsign", "false"]);
+        repo.write("git-automate.toml", config);
+        repo.write("README.md", "hello\n");
+        repo.git(&["add", "."]);
+        repo.git(&["commit", "-q", "-m", "initial commit"]);
+        // Work on a feature branch so the default protected branches don't get in the way.
+        repo.git(&["checkout", "-q", "-b", "feature"]);
+        repo
+    }
+
+    fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    fn write(&self, file: &str, contents: &str) {
+        fs::write(self.dir.join(file), contents).unwrap();
+    }
+
+    fn git(&self, args: &[&str]) -> String {
+        let output = isolated(Command::new("git")).args(args).current_dir(&self.dir).output().unwrap();
+        assert!(output.status.success(), "git {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    /// Writes an executable stand-in for git that runs `prelude` (a shell snippet
+    /// that may exit early) and otherwise hands over to the real git.
+    #[cfg(unix)]
+    fn fake_git(&self, prelude: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let real_git = Command::new("sh").args(["-c", "command -v git"]).output().unwrap();
+        let real_git = String::from_utf8_lossy(&real_git.stdout).trim().to_string();
+        let script = self.dir.join(".git").join("fake-git");
+        fs::write(&script, format!("#!/bin/sh\n{}\nexec {} \"$@\"\n", prelude, real_git)).unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        script
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        isolated(Command::new(env!("CARGO_BIN_EXE_git-automate")))
+            .args(args)
+            .current_dir(&self.dir)
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for TestRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Keeps the user's own git and git-automate configuration out of the tests.
+fn isolated(mut command: Command) -> Command {
+    command
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("XDG_CONFIG_HOME", std::env::temp_dir().join("git-automate-test-no-config"))
+        .env_remove("GIT_DIR")
+        .env_remove("GIT_WORK_TREE");
+    command
+}
+
+#[test]
+fn missing_message_file_leaves_the_index_untouched() {
+    let repo = TestRepo::new("auto_pull = false\n");
+    repo.write("README.md", "changed\n");
+    repo.write("new.txt", "new\n");
+    let before = repo.git(&["status", "--porcelain"]);
+
+    let missing = repo.path().join("missing-message.txt");
+    let output = repo.run(&["-y", "commit", "-F", missing.to_str().unwrap()]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Message file not found"));
+    assert_eq!(repo.git(&["status", "--porcelain"]), before);
+    assert_eq!(repo.git(&["diff", "--cached", "--name-only"]), "");
+}
+
+#[test]
+fn merge_conflicts_exit_with_code_4() {
+    let repo = TestRepo::new("auto_pull = false\n");
+    let main = repo.git(&["symbolic-ref", "--short", "HEAD"]);
+    repo.git(&["checkout", "-q", "-b", "other"]);
+    repo.write("README.md", "theirs\n");
+    repo.git(&["commit", "-q", "-am", "change on other"]);
+    repo.git(&["checkout", "-q", main.trim()]);
+    repo.write("README.md", "ours\n");
+    repo.git(&["commit", "-q", "-am", "change on main"]);
+
+    let output = repo.run(&["merge", "other"]);
+
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("README.md"));
+}
+
+#[test]
+fn failed_rebase_reports_the_git_error() {
+    let repo = TestRepo::new("auto_pull = false\n");
+
+    let output = repo.run(&["rebase", "no-such-branch"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Rebase onto no-such-branch failed"));
+}
+
+#[test]
+fn require_changes_ignores_changes_outside_the_given_files() {
+    let repo = TestRepo::new("auto_pull = false\n");
+    repo.write("unrelated.txt", "not part of this commit\n");
+
+    let output = repo.run(&["commit", "--files", "README.md", "--require-changes", "-m", "Update readme"]);
+
+    assert_eq!(output.status.code(), Some(3), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(repo.git(&["rev-list", "--count", "HEAD"]).trim(), "1");
+}
+
+#[test]
+fn commit_onto_dry_run_plans_the_final_commit() {
+    let repo = TestRepo::new("auto_pull = false\nsign_commits = false\n");
+    repo.git(&["branch", "target"]);
+    repo.write("README.md", "changed\n");
+
+    let output = repo.run(&[
+        "--dry-run",
+        "--format",
+        "json",
+        "commit",
+        "--onto",
+        "target",
+        "-m",
+        "tidy up",
+        "--type",
+        "fix",
+        "--sign",
+        "--co-author",
+        "Ann <ann@example.com>",
+    ]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(r#""message":"fix: tidy up\n\nCo-authored-by: Ann <ann@example.com>""#), "{}", stdout);
+    assert!(stdout.contains(r#""sign":true"#), "{}", stdout);
+}
+
+#[test]
+fn confirmation_without_a_terminal_requires_yes() {
+    let repo = TestRepo::new("auto_pull = false\n");
+    repo.write("scratch.txt", "temporary\n");
+
+    let output = repo.run(&["clean", "--force"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("pass -y/--yes"));
+    assert!(repo.path().join("scratch.txt").exists());
+
+    let output = repo.run(&["--yes", "clean", "--force"]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(!repo.path().join("scratch.txt").exists());
+}
+
+#[test]
+fn global_config_sits_under_the_project_config() {
+    let repo = TestRepo::new("auto_pull = false\n");
+    let config_home = repo.path().join(".config-home");
+    fs::create_dir_all(config_home.join("git-automate")).unwrap();
+    fs::write(config_home.join("git-automate").join("config.toml"), "default_remote = \"upstream\"\n").unwrap();
+    fs::write(repo.path().join(".git").join("info").join("exclude"), ".config-home\n").unwrap();
+    repo.write("README.md", "changed\n");
+
+    let planned_remote = |args: &[&str], project: &str| {
+        repo.write("git-automate.toml", project);
+        let mut command = isolated(Command::new(env!("CARGO_BIN_EXE_git-automate")));
+        let output = command
+            .env("XDG_CONFIG_HOME", &config_home)
+            .args(["--dry-run", "--format", "json"])
+            .args(args)
+            .args(["commit", "-m", "Update readme"])
+            .current_dir(repo.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        ["upstream", "origin"].into_iter().find(|remote| stdout.contains(&format!(r#""remote":"{}""#, remote)))
+    };
+
+    assert_eq!(planned_remote(&[], "auto_pull = false\n"), Some("upstream"));
+    assert_eq!(planned_remote(&["--no-global"], "auto_pull = false\n"), Some("origin"));
+    assert_eq!(planned_remote(&[], "auto_pull = false\ndefault_remote = \"origin\"\n"), Some("origin"));
+}
+
+#[test]
+fn empty_commit_behavior_decides_what_a_clean_tree_does() {
+    let skip = TestRepo::new("auto_pull = false\nempty_commit_behavior = \"skip\"\n");
+    let output = skip.run(&["commit", "-m", "Nothing here"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(skip.git(&["rev-list", "--count", "HEAD"]).trim(), "1");
+
+    let error = TestRepo::new("auto_pull = false\nempty_commit_behavior = \"error\"\n");
+    let output = error.run(&["commit", "-m", "Nothing here"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(error.git(&["rev-list", "--count", "HEAD"]).trim(), "1");
+
+    let allow = TestRepo::new("auto_pull = false\nempty_commit_behavior = \"allow-empty\"\n");
+    let output = allow.run(&["--dry-run", "--format", "json", "commit", "-m", "Nothing here"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains(r#""allow_empty":true"#));
+}
+
+#[cfg(unix)]
+#[test]
+fn slow_git_commands_are_killed_after_the_timeout() {
+    use std::time::{Duration, Instant};
+
+    let repo = TestRepo::new("auto_pull = false\n");
+    // exec keeps the sleep as the child that gets killed.
+    let script = repo.fake_git("[ \"$1\" = log ] && exec sleep 30");
+
+    let started = Instant::now();
+    let output = isolated(Command::new(env!("CARGO_BIN_EXE_git-automate")))
+        .env("GIT_AUTOMATE_GIT_PATH", &script)
+        .args(["--timeout", "1", "stats"])
+        .current_dir(repo.path())
+        .output()
+        .unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(10), "the slow command was not killed");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("timed out after 1s"), "{}", stderr);
+}
+
+#[test]
+fn detached_head_is_reported_without_a_lookup_error() {
+    let repo = TestRepo::new("auto_pull = false\n");
+    repo.git(&["checkout", "-q", "--detach"]);
+    repo.write("README.md", "changed\n");
+
+    // --verbose so a spurious logged lookup failure would show up in stderr.
+    let output = repo.run(&["--verbose", "commit", "-m", "Update readme"]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("detached HEAD state; check out a branch first"), "{}", stderr);
+    assert!(!stderr.contains("Current branch lookup failed"), "{}", stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn failing_branch_lookup_reports_the_git_error() {
+    let repo = TestRepo::new("auto_pull = false\n");
+    let script = repo.fake_git(
+        "case \"$1 $2\" in 'symbolic-ref --short'|'rev-parse --abbrev-ref') echo 'fatal: simulated failure' >&2; exit 128;; esac",
+    );
+    repo.write("README.md", "changed\n");
+
+    let output = isolated(Command::new(env!("CARGO_BIN_EXE_git-automate")))
+        .env("GIT_AUTOMATE_GIT_PATH", &script)
+        .args(["commit", "-m", "Update readme"])
+        .current_dir(repo.path())
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Current branch lookup failed: fatal: simulated failure"), "{}", stderr);
+}